@@ -1,38 +1,69 @@
-use std::io::Read;
+use intcode::cli::Args;
+use intcode::symbolic::Engine;
 
-fn main() {
-    let path = std::env::args()
-        .nth(1)
-        .expect("At least one command line argument is required.");
-
-    let mut code = String::new();
-    std::fs::File::open(path)
-        .expect("Could not open input file")
-        .read_to_string(&mut code)
-        .expect("Error while reading from file.");
-
-    let instructions = code
-        .trim()
-        .split(',')
-        .map(|x| str::parse::<isize>(x).unwrap())
-        .collect::<Vec<_>>();
+const TARGET: isize = 19690720;
 
-    for noun in 0..99 {
-        for verb in 0..99 {
-            let mut memory = instructions.clone();
+// Runs the program for every noun and verb, on machines that record or replay with the tap.
+fn search(args: &Args, instructions: &[isize]) -> Option<(isize, isize)> {
+    for noun in 0..100 {
+        for verb in 0..100 {
+            let mut memory = instructions.to_vec();
 
             memory[1] = noun;
             memory[2] = verb;
 
-            if intcode::evaluate(memory) == 19690720 {
-                println!(
-                    "Found noun = {} and verb = {}. Combined output is {}",
-                    noun,
-                    verb,
-                    100 * noun + verb
-                );
-                break;
+            if args.evaluate(memory) == TARGET {
+                return Some((noun, verb));
             }
         }
     }
+    None
+}
+
+// Solves for noun and verb with the symbolic engine, without running the program.
+fn solve(instructions: &[isize]) -> Option<(isize, isize)> {
+    let mut engine = Engine::new(instructions);
+    let noun = engine.variable(0, 99);
+    let verb = engine.variable(0, 99);
+    engine.patch(1, noun);
+    engine.patch(2, verb);
+
+    engine
+        .solve_cell(0, TARGET)
+        .map(|values| (values[0] as isize, values[1] as isize))
+}
+
+fn main() {
+    let args = Args::from_env();
+    let path = args
+        .rest
+        .first()
+        .expect("At least one command line argument is required.");
+
+    let instructions = args.read_program(path);
+
+    // With noun or verb patched there is nothing to search for, the program runs as given.
+    if args
+        .patches
+        .iter()
+        .any(|&(address, _)| address == 1 || address == 2)
+    {
+        println!("Cell 0 holds {}", args.evaluate(instructions));
+        return;
+    }
+
+    let found = if args.tap.is_some() {
+        search(&args, &instructions)
+    } else {
+        solve(&instructions)
+    };
+    match found {
+        Some((noun, verb)) => println!(
+            "Found noun = {} and verb = {}. Combined output is {}",
+            noun,
+            verb,
+            100 * noun + verb
+        ),
+        None => println!("No noun and verb produce {}.", TARGET),
+    }
 }
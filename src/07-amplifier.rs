@@ -1,5 +1,6 @@
+use intcode::cli::Args;
+use intcode::scheduler::{Policy, Scheduler};
 use intcode::*;
-use std::io::Read;
 
 #[test]
 fn test_next_permutation() {
@@ -25,7 +26,10 @@ fn test_compute_amplification1() {
     ];
     let setting = [4, 3, 2, 1, 0];
 
-    assert_eq!(43210, compute_amplification(&instructions, &setting))
+    assert_eq!(
+        43210,
+        compute_amplification(&Args::default(), &instructions, &setting)
+    )
 }
 
 #[test]
@@ -36,7 +40,10 @@ fn test_compute_amplification2() {
     ];
     let setting = [0, 1, 2, 3, 4];
 
-    assert_eq!(54321, compute_amplification(&instructions, &setting))
+    assert_eq!(
+        54321,
+        compute_amplification(&Args::default(), &instructions, &setting)
+    )
 }
 
 #[test]
@@ -47,7 +54,10 @@ fn test_compute_amplification3() {
     ];
     let setting = [1, 0, 4, 3, 2];
 
-    assert_eq!(65210, compute_amplification(&instructions, &setting))
+    assert_eq!(
+        65210,
+        compute_amplification(&Args::default(), &instructions, &setting)
+    )
 }
 
 #[test]
@@ -60,7 +70,7 @@ fn test_compute_feedback_amplification1() {
 
     assert_eq!(
         139629729,
-        compute_feedback_amplification(&instructions, &setting)
+        compute_feedback_amplification(&Args::default(), &instructions, &setting)
     );
 }
 
@@ -74,7 +84,7 @@ fn test_compute_feedback_amplification2() {
     let setting = [9, 7, 8, 5, 6];
     assert_eq!(
         18216,
-        compute_feedback_amplification(&instructions, &setting)
+        compute_feedback_amplification(&Args::default(), &instructions, &setting)
     );
 }
 
@@ -105,60 +115,62 @@ fn next_permutation(perm: &mut [isize]) -> bool {
     }
 }
 
-fn compute_amplification(instructions: &Vec<isize>, input: &[isize]) -> isize {
-    let mut output = 0;
-    for boost in input {
-        let data = [*boost, output];
-        let mut io = BufIo::new(&data);
-        evaluate_io(instructions.clone(), &mut io);
-        output = io.get(0);
+// Runs one amplifier per phase, each feeding the next one. With feedback, the last amplifier
+// feeds the first one, and its final output ends up in the queue of the first amplifier.
+fn amplifiers(args: &Args, instructions: &[isize], phases: &[isize], feedback: bool) -> Scheduler {
+    let mut scheduler = Scheduler::new(Policy::UntilBlocked);
+    for phase in phases {
+        let amplifier = scheduler.add(args.machine(instructions.to_vec()));
+        scheduler.send(amplifier, &[*phase]);
+        if amplifier > 0 {
+            scheduler.link(amplifier - 1, amplifier);
+        }
     }
+    if feedback {
+        scheduler.link(phases.len() - 1, 0);
+    }
+    scheduler.send(0, &[0]);
 
-    output
-}
-
-fn initialize(instructions: &Vec<isize>, input: isize) -> Intcode {
-    let mut icode = Intcode::new(instructions.clone());
-
-    assert!(icode.step(0) == State::Input);
-    assert!(icode.step(input) == State::Input);
-
-    icode
+    scheduler
 }
 
-fn feed_input(icode: &mut Intcode, input: isize) -> isize {
-    let output = match icode.step(input) {
-        State::Output(o) => o,
-        _ => panic!("Unexpected state"),
-    };
+fn compute_amplification(args: &Args, instructions: &[isize], input: &[isize]) -> isize {
+    let mut scheduler = amplifiers(args, instructions, input, false);
 
-    match icode.step(0) {
-        State::Input | State::Terminated => output,
-        _ => panic!("Unexpected State"),
+    let mut output = 0;
+    loop {
+        match scheduler.run().unwrap_or_else(|e| panic!("{}", e)) {
+            scheduler::Event::Output(_, values) => output = values[0],
+            scheduler::Event::Halted => return output,
+            scheduler::Event::Idle => panic!("Amplifiers are waiting for input"),
+            scheduler::Event::Terminated(_) => (),
+            scheduler::Event::Undeliverable(..) => unreachable!(),
+        }
     }
 }
 
-fn compute_feedback_amplification(instructions: &Vec<isize>, input: &[isize]) -> isize {
-    let mut amplifiers: Vec<_> = input.iter().map(|i| initialize(instructions, *i)).collect();
+fn compute_feedback_amplification(args: &Args, instructions: &[isize], input: &[isize]) -> isize {
+    let mut scheduler = amplifiers(args, instructions, input, true);
 
-    let mut data = 0;
-    let mut i = 0;
-    while !amplifiers[i].is_terminated() {
-        data = feed_input(&mut amplifiers[i], data);
-        i = (i + 1) % amplifiers.len();
+    loop {
+        match scheduler.run().unwrap_or_else(|e| panic!("{}", e)) {
+            scheduler::Event::Halted => break,
+            scheduler::Event::Idle => panic!("Amplifiers are waiting for input"),
+            _ => (),
+        }
     }
 
-    data
+    *scheduler.inputs(0).back().unwrap()
 }
 
-fn best_simple_amplifier_setting(instructions: &Vec<isize>) {
+fn best_simple_amplifier_setting(args: &Args, instructions: &Vec<isize>) {
     let mut input: Vec<isize> = (0..5).collect();
 
-    let mut best_val = compute_amplification(instructions, &input);
+    let mut best_val = compute_amplification(args, instructions, &input);
     let mut best = input.clone();
 
     while next_permutation(input.as_mut_slice()) {
-        let val = compute_amplification(instructions, &input);
+        let val = compute_amplification(args, instructions, &input);
         if val > best_val {
             best_val = val;
             best = input.clone();
@@ -168,14 +180,14 @@ fn best_simple_amplifier_setting(instructions: &Vec<isize>) {
     println!("Configuration {:?} achieved best value {}", best, best_val);
 }
 
-fn best_feedback_amplifier_setting(instructions: &Vec<isize>) {
+fn best_feedback_amplifier_setting(args: &Args, instructions: &Vec<isize>) {
     let mut input: Vec<isize> = (5..10).collect();
 
-    let mut best_val = compute_feedback_amplification(instructions, &input);
+    let mut best_val = compute_feedback_amplification(args, instructions, &input);
     let mut best = input.clone();
 
     while next_permutation(input.as_mut_slice()) {
-        let val = compute_feedback_amplification(instructions, &input);
+        let val = compute_feedback_amplification(args, instructions, &input);
         if val > best_val {
             best_val = val;
             best = input.clone();
@@ -188,22 +200,14 @@ fn best_feedback_amplifier_setting(instructions: &Vec<isize>) {
     );
 }
 fn main() {
-    let path = std::env::args()
-        .nth(1)
+    let args = Args::from_env();
+    let path = args
+        .rest
+        .first()
         .expect("At least one command line argument is required.");
 
-    let mut code = String::new();
-    std::fs::File::open(path)
-        .expect("Could not open input file")
-        .read_to_string(&mut code)
-        .expect("Error while reading from file.");
-
-    let instructions = code
-        .trim()
-        .split(',')
-        .map(|x| str::parse::<isize>(x).unwrap())
-        .collect::<Vec<_>>();
+    let instructions = args.read_program(path);
 
-    best_simple_amplifier_setting(&instructions);
-    best_feedback_amplifier_setting(&instructions);
+    best_simple_amplifier_setting(&args, &instructions);
+    best_feedback_amplifier_setting(&args, &instructions);
 }
@@ -1,25 +1,15 @@
-use std::io::Read;
-
 fn main() {
-    let path = std::env::args()
-        .nth(1)
+    let args = intcode::cli::Args::from_env();
+    let path = args
+        .rest
+        .first()
         .expect("At least one command line argument is required.");
 
-    let mut code = String::new();
-    std::fs::File::open(path)
-        .expect("Could not open input file")
-        .read_to_string(&mut code)
-        .expect("Error while reading from file.");
-
-    let instructions = code
-        .trim()
-        .split(',')
-        .map(|x| str::parse::<isize>(x).unwrap())
-        .collect::<Vec<_>>();
+    let instructions = args.read_program(path);
 
     let mut io = intcode::BufIo::new(&[1]);
 
-    intcode::evaluate_io(instructions.clone(), &mut io);
+    args.evaluate_io(instructions.clone(), &mut io);
 
     if io.len() > 1 {
         println!("ERROR: The following instructions do not work properly:");
@@ -31,7 +21,7 @@ fn main() {
     }
 
     let mut io = intcode::BufIo::new(&[2]);
-    intcode::evaluate_io(instructions, &mut io);
+    args.evaluate_io(instructions, &mut io);
 
     println!("The distress signal coordinates are {}", io.get(0));
 }
@@ -1,22 +1,13 @@
 use std::collections::HashSet;
-use std::io::Read;
 
 fn main() {
-    let path = std::env::args()
-        .nth(1)
+    let args = intcode::cli::Args::from_env();
+    let path = args
+        .rest
+        .first()
         .expect("At least one command line argument is required.");
 
-    let mut code = String::new();
-    std::fs::File::open(path)
-        .expect("Could not open input file")
-        .read_to_string(&mut code)
-        .expect("Error while reading from file.");
-
-    let instructions = code
-        .trim()
-        .split(',')
-        .map(|x| str::parse::<isize>(x).unwrap())
-        .collect::<Vec<_>>();
+    let instructions = args.read_program(path);
 
     let mut white = HashSet::new();
     white.insert((0, 0));
@@ -24,37 +15,34 @@ fn main() {
 
     let mut position = (0isize, 0isize);
     let mut direction = (0, -1);
-    let mut interpreter = intcode::Intcode::new(instructions);
+    let interpreter = args.machine(instructions);
+    let mut robot = intcode::framing::tuples::<(isize, isize)>(interpreter);
 
-    let mut has_painted = false;
     let mut input = 0;
 
     loop {
-        match (interpreter.step(input), has_painted) {
-            (intcode::State::Input, _) => input = white.contains(&position) as isize,
-            (intcode::State::Output(o), false) => {
+        match robot.step(input) {
+            intcode::State::Input => input = white.contains(&position) as isize,
+            intcode::State::Output((color, turn)) => {
                 painted.insert(position);
 
-                if o == 0 {
+                if color == 0 {
                     white.remove(&position);
                 } else {
                     white.insert(position);
                 }
-                has_painted = true;
-            }
-            (intcode::State::Output(o), true) => {
-                if o == 0 {
+
+                if turn == 0 {
                     direction = (direction.1, -direction.0);
-                } else if o == 1 {
+                } else if turn == 1 {
                     direction = (-direction.1, direction.0);
                 } else {
                     panic!("Unhandled direction change");
                 }
 
                 position = (position.0 + direction.0, position.1 + direction.1);
-                has_painted = false;
             }
-            (intcode::State::Terminated, _) => break,
+            intcode::State::Terminated => break,
         }
     }
 
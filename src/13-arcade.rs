@@ -1,9 +1,11 @@
+use intcode::cli::Args;
+use intcode::recording::SharedTap;
 use intcode::State;
 use std::io::{BufRead, Read, Write};
 
-fn count_block_tiles(instructions: Vec<isize>) {
+fn count_block_tiles(args: &Args, instructions: Vec<isize>) {
     let mut io = intcode::BufIo::new(&[]);
-    intcode::evaluate_io(instructions, &mut io);
+    args.evaluate_io(instructions, &mut io);
 
     let mut i = 0;
     let mut count = 0;
@@ -37,7 +39,9 @@ fn print_game(field: &[char; 43 * 23], score: isize) {
     }
 }
 
-fn cheat(instructions: &Vec<isize>) {
+// Lost games are continued from an earlier move. Use --record and --replay to continue a game
+// in a later session.
+fn cheat(args: &Args, instructions: &Vec<isize>) {
     let mut input_sequence = Vec::new();
 
     if let Ok(input_file) = std::fs::File::open("arcade_dump.txt") {
@@ -46,8 +50,14 @@ fn cheat(instructions: &Vec<isize>) {
         }
     }
 
+    // All games are the same machine to the tap, a lost game is replaced by the next one.
+    let tap = args
+        .tap
+        .as_ref()
+        .map(|tap| (tap.clone(), tap.lock().unwrap().attach()));
+
     loop {
-        let result = play_game(instructions.clone(), &mut input_sequence);
+        let result = play_game(&tap, instructions.clone(), &mut input_sequence);
 
         {
             let mut output = std::fs::File::create("arcade_dump.txt").unwrap();
@@ -69,6 +79,9 @@ fn cheat(instructions: &Vec<isize>) {
                 };
 
                 input_sequence.truncate(input_sequence.len() - time);
+                if let Some((tap, machine)) = &tap {
+                    tap.lock().unwrap().step_back(*machine, 0);
+                }
             }
             Some(score) => {
                 println!("You beat the game with a score of {}", score);
@@ -78,24 +91,33 @@ fn cheat(instructions: &Vec<isize>) {
     }
 }
 
-fn play_game(mut instructions: Vec<isize>, input_sequence: &mut Vec<isize>) -> Option<isize> {
-    instructions[0] = 2;
+fn play_game(
+    tap: &Option<(SharedTap, usize)>,
+    instructions: Vec<isize>,
+    input_sequence: &mut Vec<isize>,
+) -> Option<isize> {
     let mut interpreter = intcode::Intcode::new(instructions);
+    if let Some((tap, machine)) = tap {
+        interpreter.set_tap(tap.clone(), *machine);
+    }
+    interpreter.poke(0, 2).unwrap();
+    let mut arcade = intcode::framing::tuples::<(isize, isize, isize)>(interpreter);
 
     let mut input = 0;
     let mut input_buffer = [0; 1];
-    let mut output_buffer = [0; 3];
 
-    let mut n_output = 0;
     let mut field = [' '; 43 * 23];
     let mut score = 0;
 
     let mut moves = 0;
     loop {
-        match interpreter.step(input) {
+        match arcade.step(input) {
             State::Input => {
                 if moves < input_sequence.len() {
                     input = input_sequence[moves];
+                } else if let Some(recorded) = arcade.interpreter().recorded_input() {
+                    input = recorded;
+                    input_sequence.push(input);
                 } else {
                     print_game(&field, score);
                     loop {
@@ -113,18 +135,11 @@ fn play_game(mut instructions: Vec<isize>, input_sequence: &mut Vec<isize>) -> O
 
                 moves += 1;
             }
-            State::Output(o) => {
-                output_buffer[n_output] = o;
-
-                if n_output == 2 {
-                    if output_buffer[0] == -1 && output_buffer[1] == 0 {
-                        score = output_buffer[2];
-                    } else {
-                        update_field(&mut field, &output_buffer);
-                    }
-                    n_output = 0;
+            State::Output((x, y, tile)) => {
+                if x == -1 && y == 0 {
+                    score = tile;
                 } else {
-                    n_output += 1;
+                    update_field(&mut field, &[x, y, tile]);
                 }
             }
             State::Terminated => {
@@ -141,21 +156,20 @@ fn play_game(mut instructions: Vec<isize>, input_sequence: &mut Vec<isize>) -> O
     }
 }
 
-fn play_game_ai(mut instructions: Vec<isize>) {
+fn play_game_ai(args: &Args, instructions: Vec<isize>) {
     use std::cmp::Ordering;
 
     let mut ball_pos = 0;
     let mut player_pos = 0;
     let mut score = 0;
-    let mut n_output = 0;
-    let mut output_buffer = [0; 3];
     let mut input = 0;
 
-    instructions[0] = 2;
-    let mut interpreter = intcode::Intcode::new(instructions);
+    let mut interpreter = args.machine(instructions);
+    interpreter.poke(0, 2).unwrap();
+    let mut arcade = intcode::framing::tuples::<(isize, isize, isize)>(interpreter);
     let mut steps = 0;
     loop {
-        match interpreter.step(input) {
+        match arcade.step(input) {
             State::Input => {
                 steps += 1;
                 input = match ball_pos.cmp(&player_pos) {
@@ -164,19 +178,13 @@ fn play_game_ai(mut instructions: Vec<isize>) {
                     Ordering::Greater => 1,
                 };
             }
-            State::Output(o) => {
-                output_buffer[n_output] = o;
-                if n_output == 2 {
-                    if output_buffer[0] == -1 && output_buffer[1] == 0 {
-                        score = output_buffer[2];
-                    } else if output_buffer[2] == 4 {
-                        ball_pos = output_buffer[0];
-                    } else if output_buffer[2] == 3 {
-                        player_pos = output_buffer[0];
-                    }
-                    n_output = 0;
-                } else {
-                    n_output += 1;
+            State::Output((x, y, tile)) => {
+                if x == -1 && y == 0 {
+                    score = tile;
+                } else if tile == 4 {
+                    ball_pos = x;
+                } else if tile == 3 {
+                    player_pos = x;
                 }
             }
             State::Terminated => {
@@ -188,12 +196,14 @@ fn play_game_ai(mut instructions: Vec<isize>) {
 }
 
 fn main() {
-    let path = std::env::args()
-        .nth(1)
+    let args = Args::from_env();
+    let path = args
+        .rest
+        .first()
         .expect("At least one command line argument is required.");
 
-    let instructions = intcode::read_intcode_file(&path);
-    count_block_tiles(instructions.clone());
-    play_game_ai(instructions.clone());
-    cheat(&instructions);
+    let instructions = args.read_program(path);
+    count_block_tiles(&args, instructions.clone());
+    play_game_ai(&args, instructions.clone());
+    cheat(&args, &instructions);
 }
@@ -135,12 +135,14 @@ fn explore(
 }
 
 fn main() {
-    let path = std::env::args()
-        .nth(1)
+    let args = intcode::cli::Args::from_env();
+    let path = args
+        .rest
+        .first()
         .expect("At least one command line argument is required.");
 
-    let instructions = intcode::read_intcode_file(&path);
-    let mut interpreter = intcode::Intcode::new(instructions.clone());
+    let instructions = args.read_program(path);
+    let mut interpreter = args.machine(instructions.clone());
     interpreter.step(-1);
 
     let mut robot = move |dir| {
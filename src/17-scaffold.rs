@@ -147,14 +147,16 @@ fn trace(map: &Vec<isize>) -> Vec<isize> {
 }
 
 fn main() {
-    let path = std::env::args()
-        .nth(1)
+    let args = intcode::cli::Args::from_env();
+    let path = args
+        .rest
+        .first()
         .expect("At least one command line argument is required.");
 
-    let mut instructions = intcode::read_intcode_file(&path);
+    let instructions = args.read_program(path);
 
     let mut io = intcode::BufIo::new(&[]);
-    intcode::evaluate_io(instructions.clone(), &mut io);
+    args.evaluate_io(instructions.clone(), &mut io);
     print_map(io.output());
 
     let intersections = compute_intersections(io.output());
@@ -184,8 +186,8 @@ n\n";
         .collect::<Vec<_>>();
     let mut io = intcode::BufIo::new(input.as_slice());
 
-    instructions[0] = 2;
-
-    intcode::evaluate_io(instructions, &mut io);
+    let mut interpreter = args.machine(instructions);
+    interpreter.poke(0, 2).unwrap();
+    interpreter.run(&mut io).unwrap_or_else(|e| panic!("{}", e));
     println!("Dust collected {}", io.output()[io.output().len() - 1]);
 }
@@ -1,22 +1,26 @@
-fn evaluate_beam(instructions: Vec<isize>, i: isize, j: isize) -> isize {
+use intcode::cli::Args;
+
+fn evaluate_beam(args: &Args, instructions: Vec<isize>, i: isize, j: isize) -> isize {
     let tmp = [i, j];
     let mut io = intcode::BufIo::new(&tmp);
-    intcode::evaluate_io(instructions, &mut io);
+    args.evaluate_io(instructions, &mut io);
 
     io.get(0)
 }
 
 fn main() {
-    let path = std::env::args()
-        .nth(1)
+    let args = Args::from_env();
+    let path = args
+        .rest
+        .first()
         .expect("At least one command line argument is required.");
 
-    let instructions = intcode::read_intcode_file(&path);
+    let instructions = args.read_program(path);
 
     let mut sum = 0;
     for i in 0..50 {
         for j in 0..50 {
-            sum += evaluate_beam(instructions.clone(), j, i);
+            sum += evaluate_beam(&args, instructions.clone(), j, i);
         }
     }
     println!("The mount of affected fields is {}", sum);
@@ -25,14 +29,14 @@ fn main() {
     let mut max_j = 0;
     for i in 10..1000 {
         let mut j = min_j;
-        while evaluate_beam(instructions.clone(), j, i) == 0 {
+        while evaluate_beam(&args, instructions.clone(), j, i) == 0 {
             j += 1;
         }
 
         min_j = j;
         j = std::cmp::max(max_j, j);
 
-        while evaluate_beam(instructions.clone(), j, i) == 1 {
+        while evaluate_beam(&args, instructions.clone(), j, i) == 1 {
             j += 1;
         }
 
@@ -44,7 +48,7 @@ fn main() {
 
         let mut all_covered = true;
         for j in (max_j - 100)..max_j {
-            if evaluate_beam(instructions.clone(), j, i + 99) == 0 {
+            if evaluate_beam(&args, instructions.clone(), j, i + 99) == 0 {
                 all_covered = false;
                 break;
             }
@@ -1,10 +1,12 @@
+use intcode::cli::Args;
+
 fn print_output(map: &[isize]) {
     let output = String::from_utf8(map.iter().map(|x| *x as u8).collect()).unwrap();
 
     println!("{}", output);
 }
 
-fn run(instructions: Vec<isize>, buffer: &str) {
+fn run(args: &Args, instructions: Vec<isize>, buffer: &str) {
     let input = buffer
         .as_bytes()
         .iter()
@@ -12,7 +14,7 @@ fn run(instructions: Vec<isize>, buffer: &str) {
         .collect::<Vec<_>>();
     let mut io = intcode::BufIo::new(input.as_slice());
 
-    intcode::evaluate_io(instructions, &mut io);
+    args.evaluate_io(instructions, &mut io);
 
     // Check that all output is ASCII. If not, the robot was successful and we can report the
     // damage assessment.
@@ -24,11 +26,13 @@ fn run(instructions: Vec<isize>, buffer: &str) {
 }
 
 fn main() {
-    let path = std::env::args()
-        .nth(1)
+    let args = Args::from_env();
+    let path = args
+        .rest
+        .first()
         .expect("At least one command line argument is required.");
 
-    let instructions = intcode::read_intcode_file(&path);
+    let instructions = args.read_program(path);
 
     println!("Part 1");
     let buffer_1 = "NOT C J
@@ -36,7 +40,7 @@ AND D J
 NOT A T
 OR T J
 WALK\n";
-    run(instructions.clone(), buffer_1);
+    run(&args, instructions.clone(), buffer_1);
 
     println!("Part 2");
     let buffer_2 = "NOT C J
@@ -49,5 +53,5 @@ AND D T
 OR T J
 RUN\n";
 
-    run(instructions, buffer_2);
+    run(&args, instructions, buffer_2);
 }
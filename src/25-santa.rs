@@ -1,8 +1,10 @@
 fn main() {
-    let path = std::env::args()
-        .nth(1)
+    let args = intcode::cli::Args::from_env();
+    let path = args
+        .rest
+        .first()
         .expect("At least one command line argument is required.");
 
-    let instructions = intcode::read_intcode_file(&path);
-    intcode::evaluate_io(instructions, &mut intcode::AsciiIo::new());
+    let instructions = args.read_program(path);
+    args.evaluate_io(instructions, &mut intcode::AsciiIo::new());
 }
@@ -0,0 +1,570 @@
+use crate::disasm::{Instruction, Op, Param};
+use std::collections::HashMap;
+use std::fmt;
+
+#[test]
+fn test_assemble() {
+    let source = "
+        ; echo numbers until a zero is read
+        .var value, 1
+                ARB #100
+        start:  IN [value]
+                JZ [rb+1], #end
+                OUT [rb+value]
+                JNZ #1, #start
+        end:    HLT
+    ";
+
+    let program = assemble(source).unwrap();
+    assert_eq!(
+        vec![109, 100, 203, 1, 1206, 1, 12, 204, 1, 1105, 1, 2, 99],
+        program
+    );
+
+    let mut io = crate::BufIo::new(&[4, 5, 0]);
+    crate::evaluate_io(program, &mut io);
+    assert_eq!(&vec![4, 5], io.output());
+}
+
+#[test]
+fn test_data() {
+    let source = "
+        ptr:    .data 3, -1, msg
+        msg:    .string \"Hi\\n\"
+                DATA 7
+    ";
+    assert_eq!(vec![3, -1, 3, 72, 105, 10, 7], assemble(source).unwrap());
+}
+
+#[test]
+fn test_macro() {
+    let source = "
+        .macro COPY src, dst
+            ADD src, #0, dst
+        .endm
+        .macro COUNT_DOWN counter
+        loop@:  OUT counter
+                ADD counter, #-1, counter
+                JNZ counter, #loop@
+        .endm
+                COPY #2, [n]
+                COUNT_DOWN [n]
+                HLT
+        n:      .data 0
+    ";
+
+    let program = assemble(source).unwrap();
+    let mut io = crate::BufIo::new(&[]);
+    crate::evaluate_io(program, &mut io);
+    assert_eq!(&vec![2, 1], io.output());
+}
+
+#[test]
+fn test_round_trip() {
+    let program = vec![
+        3, 52, 1001, 52, -5, 52, 3, 53, 1, 52, 56, 54, 1007, 54, 5, 55, 1005, 55, 26, 1001, 54, -5,
+        54, 1105, 1, 12, 1, 53, 54, 53, 1008, 54, 0, 55, 1001, 55, 1, 55, 2, 53, 55, 53, 4, 53,
+        1001, 56, -1, 56, 1005, 56, 6, 99, 0, 0, 0, 0, 10, 109, -1, 204, 3, 11101,
+    ];
+
+    assert_eq!(program, assemble(&crate::disasm::source(&program)).unwrap());
+
+    let program = vec![
+        1201,
+        isize::MIN,
+        isize::MIN,
+        0,
+        104,
+        isize::MIN,
+        99,
+        isize::MIN,
+    ];
+    assert_eq!(program, assemble(&crate::disasm::source(&program)).unwrap());
+}
+
+#[test]
+fn test_relative_offsets() {
+    let program = assemble("OUT [rb-1+2]\nOUT [rb - 2 - 3]\nOUT [rb+x-1]\nx: HLT").unwrap();
+    assert_eq!(vec![204, 1, 204, -5, 204, 5, 99], program);
+}
+
+#[test]
+fn test_errors() {
+    assert_eq!(3, assemble("HLT\n\n  JNZ #1, #nowhere").unwrap_err().line);
+    assert_eq!(1, assemble("ADD #1, #2, #3").unwrap_err().line);
+    assert_eq!(2, assemble("a: HLT\na: HLT").unwrap_err().line);
+    assert_eq!(1, assemble("FOO [1]").unwrap_err().line);
+    assert_eq!(1, assemble("OUT 5").unwrap_err().line);
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+fn error<T>(line: usize, message: String) -> Result<T, AsmError> {
+    Err(AsmError { line, message })
+}
+
+// Nested macro invocations are expanded up to this depth.
+const MAX_MACRO_DEPTH: usize = 16;
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<(usize, String)>,
+}
+
+#[derive(Debug)]
+enum Operand {
+    Position(String),
+    Immediate(String),
+    Relative(String),
+}
+
+#[derive(Debug)]
+enum Statement {
+    Code(Op, Vec<Operand>),
+    Data(Vec<String>),
+    Text(Vec<isize>),
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Code(op, _) => op.arity() + 1,
+            Statement::Data(values) => values.len(),
+            Statement::Text(text) => text.len(),
+        }
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '@'
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.chars().next().is_some_and(|c| !c.is_ascii_digit())
+        && s.chars().all(|c| is_ident_char(c) && c != '@')
+}
+
+// Splits at top level commas, ignoring commas inside string literals.
+fn split_args(s: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in s.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ',' if !in_string => {
+                result.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => (),
+        }
+        current.push(c);
+    }
+
+    if !current.trim().is_empty() || !result.is_empty() {
+        result.push(current.trim().to_string());
+    }
+    result
+}
+
+// Replaces whole identifiers according to the given substitutions.
+fn substitute(line: &str, subst: &HashMap<&str, &str>, suffix: &str) -> String {
+    let mut result = String::new();
+    let mut word = String::new();
+
+    let flush = |word: &mut String, result: &mut String| {
+        match subst.get(word.as_str()) {
+            Some(value) => result.push_str(value),
+            None => result.push_str(&word.replace('@', suffix)),
+        }
+        word.clear();
+    };
+
+    for c in line.chars() {
+        if is_ident_char(c) {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut result);
+            result.push(c);
+        }
+    }
+    flush(&mut word, &mut result);
+
+    result
+}
+
+fn split_head(line: &str) -> (&str, &str) {
+    match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim()),
+        None => (line, ""),
+    }
+}
+
+struct Preprocessor {
+    macros: HashMap<String, Macro>,
+    invocations: usize,
+}
+
+impl Preprocessor {
+    fn collect(&mut self, source: &str) -> Result<Vec<(usize, String)>, AsmError> {
+        let mut lines = Vec::new();
+        let mut current: Option<(usize, String, Macro)> = None;
+
+        for (i, line) in source.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            let (head, rest) = split_head(line);
+
+            if head == ".macro" {
+                if current.is_some() {
+                    return error(i + 1, "Nested macro definition".to_string());
+                }
+                let (name, params) = split_head(rest);
+                if !is_identifier(name) {
+                    return error(i + 1, format!("Invalid macro name '{}'", name));
+                }
+                let params = split_args(params);
+                current = Some((
+                    i + 1,
+                    name.to_uppercase(),
+                    Macro {
+                        params,
+                        body: vec![],
+                    },
+                ));
+            } else if head == ".endm" {
+                match current.take() {
+                    Some((_, name, m)) => {
+                        self.macros.insert(name, m);
+                    }
+                    None => return error(i + 1, ".endm without .macro".to_string()),
+                }
+            } else if let Some((_, _, m)) = current.as_mut() {
+                m.body.push((i + 1, line.to_string()));
+            } else if !line.is_empty() {
+                lines.push((i + 1, line.to_string()));
+            }
+        }
+
+        if let Some((line, name, _)) = current {
+            return error(line, format!("Macro {} is missing .endm", name));
+        }
+
+        Ok(lines)
+    }
+
+    fn expand(
+        &mut self,
+        lines: Vec<(usize, String)>,
+        depth: usize,
+    ) -> Result<Vec<(usize, String)>, AsmError> {
+        let mut result = Vec::new();
+
+        for (number, line) in lines {
+            let (labels, rest) = split_labels(&line);
+            let (head, args) = split_head(rest);
+
+            let m = match self.macros.get(&head.to_uppercase()) {
+                Some(m) => m,
+                None => {
+                    result.push((number, line));
+                    continue;
+                }
+            };
+
+            if depth >= MAX_MACRO_DEPTH {
+                return error(number, format!("Macro {} nested too deeply", head));
+            }
+
+            let args = split_args(args);
+            if args.len() != m.params.len() {
+                return error(
+                    number,
+                    format!("Macro {} expects {} arguments", head, m.params.len()),
+                );
+            }
+
+            self.invocations += 1;
+            let suffix = format!("_{}", self.invocations);
+            let subst: HashMap<_, _> = m
+                .params
+                .iter()
+                .map(|p| p.as_str())
+                .zip(args.iter().map(|a| a.as_str()))
+                .collect();
+
+            let mut body: Vec<_> = m
+                .body
+                .iter()
+                .map(|(_, l)| (number, substitute(l, &subst, &suffix)))
+                .collect();
+            if !labels.is_empty() {
+                body.insert(
+                    0,
+                    (number, labels.iter().map(|l| format!("{}:", l)).collect()),
+                );
+            }
+
+            result.extend(self.expand(body, depth + 1)?);
+        }
+
+        Ok(result)
+    }
+}
+
+fn split_labels(mut line: &str) -> (Vec<&str>, &str) {
+    let mut labels = Vec::new();
+    while let Some(i) = line.find(':') {
+        let label = line[..i].trim();
+        if !is_identifier(label) {
+            break;
+        }
+        labels.push(label);
+        line = line[(i + 1)..].trim();
+    }
+    (labels, line)
+}
+
+fn parse_string(number: usize, s: &str) -> Result<Vec<isize>, AsmError> {
+    if s.len() < 2 || !s.starts_with('"') || !s.ends_with('"') {
+        return error(number, format!("Invalid string literal {}", s));
+    }
+
+    let mut result = Vec::new();
+    let mut chars = s[1..(s.len() - 1)].chars();
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some(c @ '\\') | Some(c @ '"') => c,
+                _ => return error(number, format!("Invalid escape sequence in {}", s)),
+            }
+        } else {
+            c
+        };
+        result.push(c as isize);
+    }
+    Ok(result)
+}
+
+fn parse_operand(
+    number: usize,
+    s: &str,
+    vars: &HashMap<String, isize>,
+) -> Result<Operand, AsmError> {
+    if let Some(expr) = s.strip_prefix('#') {
+        return Ok(Operand::Immediate(expr.trim().to_string()));
+    }
+
+    if !s.starts_with('[') || !s.ends_with(']') {
+        return error(number, format!("Operand '{}' needs a parameter mode", s));
+    }
+
+    let inner = s[1..(s.len() - 1)].trim();
+    if vars.contains_key(inner) {
+        return Ok(Operand::Relative(inner.to_string()));
+    }
+    if inner == "rb" {
+        return Ok(Operand::Relative("0".to_string()));
+    }
+
+    // The sign belongs to the first term of the offset only.
+    match inner.strip_prefix("rb").map(str::trim) {
+        Some(offset) if offset.starts_with('+') || offset.starts_with('-') => {
+            Ok(Operand::Relative(offset.to_string()))
+        }
+        _ => Ok(Operand::Position(inner.to_string())),
+    }
+}
+
+fn evaluate(
+    number: usize,
+    expr: &str,
+    symbols: &HashMap<String, isize>,
+) -> Result<isize, AsmError> {
+    let mut value = 0isize;
+    let mut sign = 1;
+    let mut term = String::new();
+
+    // Numbers are parsed with their sign, so that the most negative one can be written.
+    let evaluate_term = |term: &str, sign: isize| match term.trim() {
+        "" => error(number, format!("Invalid expression '{}'", expr)),
+        t => match format!("{}{}", if sign < 0 { "-" } else { "" }, t).parse::<isize>() {
+            Ok(v) => Ok(v),
+            Err(_) => match symbols.get(t) {
+                Some(&v) => Ok(sign.wrapping_mul(v)),
+                None => error(number, format!("Undefined symbol '{}'", t)),
+            },
+        },
+    };
+
+    for c in expr.chars() {
+        match c {
+            '+' | '-' if !term.trim().is_empty() => {
+                value = value.wrapping_add(evaluate_term(&term, sign)?);
+                term.clear();
+                sign = if c == '+' { 1 } else { -1 };
+            }
+            '-' => sign = -sign,
+            '+' => (),
+            _ => term.push(c),
+        }
+    }
+    value = value.wrapping_add(evaluate_term(&term, sign)?);
+
+    Ok(value)
+}
+
+// Assembles mnemonic source into an Intcode program.
+//
+// Every line may start with labels ("name:"), followed by an instruction, a directive or a macro
+// invocation. Comments start with ';'. Operands are written as "[expr]" (position), "#expr"
+// (immediate) or "[rb+expr]" (relative), expressions add and subtract numbers and labels.
+//
+//   .var name, offset      names an offset to the relative base, "[name]" is then relative
+//   .data a, b, ...        emits the given values, DATA is an alias
+//   .string "text"         emits the characters of the text
+//   .macro NAME a, b       starts a macro definition that ends with .endm, "@" in labels of the
+//                          body is replaced by a suffix unique for each invocation
+pub fn assemble(source: &str) -> Result<Vec<isize>, AsmError> {
+    let mut preprocessor = Preprocessor {
+        macros: HashMap::new(),
+        invocations: 0,
+    };
+    let lines = preprocessor.collect(source)?;
+    let lines = preprocessor.expand(lines, 0)?;
+
+    let mut symbols = HashMap::new();
+    let mut vars = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = 0;
+
+    for (number, line) in &lines {
+        let number = *number;
+        let (line_labels, rest) = split_labels(line);
+
+        for label in line_labels {
+            if symbols.contains_key(label) {
+                return error(number, format!("Duplicate symbol '{}'", label));
+            }
+            symbols.insert(label.to_string(), address as isize);
+        }
+
+        if rest.is_empty() {
+            continue;
+        }
+
+        let (head, args) = split_head(rest);
+        let statement = match head {
+            ".var" => {
+                let args = split_args(args);
+                let offset = args.get(1).and_then(|o| o.parse().ok());
+                match (args.len(), offset) {
+                    (2, Some(offset)) if is_identifier(&args[0]) => {
+                        if symbols.contains_key(&args[0]) {
+                            return error(number, format!("Duplicate symbol '{}'", args[0]));
+                        }
+                        symbols.insert(args[0].clone(), offset);
+                        vars.insert(args[0].clone(), offset);
+                    }
+                    _ => return error(number, ".var expects a name and an offset".to_string()),
+                }
+                continue;
+            }
+            ".data" => Statement::Data(split_args(args)),
+            ".string" => Statement::Text(parse_string(number, args)?),
+            h if h.eq_ignore_ascii_case("DATA") => Statement::Data(split_args(args)),
+            h => {
+                let op = match Op::from_name(h) {
+                    Some(op) => op,
+                    None => return error(number, format!("Unknown instruction '{}'", h)),
+                };
+
+                let args = split_args(args);
+                if args.len() != op.arity() {
+                    return error(
+                        number,
+                        format!("{} expects {} operands", op.name(), op.arity()),
+                    );
+                }
+
+                let operands = args
+                    .iter()
+                    .map(|a| parse_operand(number, a, &vars))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                if let Some(Operand::Immediate(_)) = op.output().map(|i| &operands[i]) {
+                    return error(
+                        number,
+                        format!("{} cannot write to an immediate", op.name()),
+                    );
+                }
+
+                Statement::Code(op, operands)
+            }
+        };
+
+        address += statement.size();
+        statements.push((number, statement));
+    }
+
+    let mut program = Vec::with_capacity(address);
+    for (number, statement) in statements {
+        match statement {
+            Statement::Code(op, operands) => {
+                let params = operands
+                    .iter()
+                    .map(|o| match o {
+                        Operand::Position(e) => evaluate(number, e, &symbols).map(Param::Position),
+                        Operand::Immediate(e) => {
+                            evaluate(number, e, &symbols).map(Param::Immediate)
+                        }
+                        Operand::Relative(e) => evaluate(number, e, &symbols).map(Param::Relative),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                program.extend(Instruction { op, params }.encode());
+            }
+            Statement::Data(values) => {
+                for v in values {
+                    program.push(evaluate(number, &v, &symbols)?);
+                }
+            }
+            Statement::Text(text) => program.extend(text),
+        }
+    }
+
+    Ok(program)
+}
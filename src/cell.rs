@@ -0,0 +1,59 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::Hash;
+
+#[test]
+fn test_cell() {
+    assert_eq!(None, Cell::checked_add(isize::MAX, 1));
+    assert_eq!(isize::MIN, Cell::wrapping_add(isize::MAX, 1));
+    assert_eq!(Some(1 << 100), Cell::checked_mul(1i128 << 50, 1 << 50));
+    assert_eq!(None, (1i128 << 100).to_isize());
+    assert_eq!(Some(-5), i128::from_isize(-5).to_isize());
+}
+
+// Value type of the memory cells. Addresses, opcodes and the relative base are isize for every
+// cell type, cell values that do not fit into an isize cannot be used as such.
+pub trait Cell:
+    Copy + Ord + Hash + Default + fmt::Debug + fmt::Display + Send + Sync + 'static
+{
+    fn from_isize(value: isize) -> Self;
+    fn to_isize(self) -> Option<isize>;
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_mul(self, other: Self) -> Self;
+}
+
+macro_rules! impl_cell {
+    ($($t:ty),*) => {
+        $(
+            impl Cell for $t {
+                fn from_isize(value: isize) -> $t {
+                    value as $t
+                }
+
+                fn to_isize(self) -> Option<isize> {
+                    isize::try_from(self).ok()
+                }
+
+                fn checked_add(self, other: $t) -> Option<$t> {
+                    <$t>::checked_add(self, other)
+                }
+
+                fn checked_mul(self, other: $t) -> Option<$t> {
+                    <$t>::checked_mul(self, other)
+                }
+
+                fn wrapping_add(self, other: $t) -> $t {
+                    <$t>::wrapping_add(self, other)
+                }
+
+                fn wrapping_mul(self, other: $t) -> $t {
+                    <$t>::wrapping_mul(self, other)
+                }
+            }
+        )*
+    };
+}
+
+impl_cell!(isize, i128);
@@ -0,0 +1,240 @@
+// Static control-flow graph of an Intcode program. Starting at address 0, the program is split
+// into basic blocks at the targets of JNZ/JZ and after every jump. Only immediate jump targets
+// can be followed, jumps through memory or the relative base are flagged as indirect.
+
+use crate::disasm::{Instruction, Op, Param};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+#[test]
+fn test_blocks() {
+    let program = crate::asm::assemble(
+        "
+                IN [n]
+        loop:   OUT [n]
+                ADD [n], #-1, [n]
+                JNZ [n], #loop
+                JZ #0, [ret]
+        n:      .data 0
+        ret:    .data 0
+        ",
+    )
+    .unwrap();
+    let cfg = Cfg::build(&program);
+
+    assert_eq!(
+        vec![0, 2, 11],
+        cfg.blocks.keys().copied().collect::<Vec<_>>()
+    );
+    assert_eq!(vec![Edge::Fallthrough(2)], cfg.blocks[&0].edges);
+    assert_eq!(
+        vec![Edge::Jump(2), Edge::Fallthrough(11)],
+        cfg.blocks[&2].edges
+    );
+    assert_eq!(3, cfg.blocks[&2].instructions.len());
+    assert!(cfg.blocks[&11].edges.is_empty());
+    assert_eq!(vec![11], cfg.indirect_jumps());
+}
+
+#[test]
+fn test_dot() {
+    let cfg = Cfg::build(&[1105, 1, 4, 42, 1006, 8, 3, 99, 0]);
+
+    assert_eq!(
+        "digraph intcode {\n    \
+             node [shape=box, fontname=\"monospace\"];\n    \
+             b0 [label=\"0: JNZ #1, #4\\l\"];\n    \
+             b0 -> b4 [label=\"jump\"];\n    \
+             b3 [label=\"3: invalid\\l\", color=red];\n    \
+             b4 [label=\"4: JZ [8], #3\\l\"];\n    \
+             b4 -> b3 [label=\"jump\"];\n    \
+             b4 -> b7;\n    \
+             b7 [label=\"7: HLT\\l\"];\n\
+         }\n",
+        cfg.to_dot()
+    );
+    assert!(cfg.indirect_jumps().is_empty());
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Edge {
+    // Execution continues with the next instruction.
+    Fallthrough(usize),
+    // A jump with an immediate target is taken.
+    Jump(usize),
+}
+
+impl Edge {
+    pub fn target(self) -> usize {
+        match self {
+            Edge::Fallthrough(t) | Edge::Jump(t) => t,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Block {
+    pub start: usize,
+    pub instructions: Vec<(usize, Instruction)>,
+    pub edges: Vec<Edge>,
+    // The block ends in a jump whose target is only known at runtime.
+    pub indirect: bool,
+    // The block starts at a cell that does not hold a valid instruction, it has no instructions.
+    pub invalid: bool,
+}
+
+pub struct Cfg {
+    pub blocks: BTreeMap<usize, Block>,
+}
+
+// Successors of a single instruction and whether it may jump to an unknown target.
+fn flow(address: usize, instr: &Instruction) -> (Vec<Edge>, bool) {
+    let next = address + instr.size();
+    match instr.op {
+        Op::Hlt => (vec![], false),
+        Op::Jnz | Op::Jz => {
+            // Some(true) if the jump is always taken, Some(false) if never.
+            let taken = match instr.params[0] {
+                Param::Immediate(v) => Some((v != 0) == (instr.op == Op::Jnz)),
+                _ => None,
+            };
+
+            let mut edges = Vec::new();
+            let mut indirect = false;
+            if taken != Some(false) {
+                match instr.params[1] {
+                    Param::Immediate(t) if t >= 0 => edges.push(Edge::Jump(t as usize)),
+                    // A negative target faults at runtime.
+                    Param::Immediate(_) => (),
+                    _ => indirect = true,
+                }
+            }
+            if taken != Some(true) {
+                edges.push(Edge::Fallthrough(next));
+            }
+            (edges, indirect)
+        }
+        _ => (vec![Edge::Fallthrough(next)], false),
+    }
+}
+
+fn ends_block(instr: &Instruction) -> bool {
+    matches!(instr.op, Op::Jnz | Op::Jz | Op::Hlt)
+}
+
+impl Cfg {
+    pub fn build(program: &[isize]) -> Cfg {
+        Cfg::build_from(program, &[0])
+    }
+
+    // Builds the graph of the code reachable from any of the given entry points.
+    pub fn build_from(program: &[isize], entries: &[usize]) -> Cfg {
+        let mut code = HashMap::new();
+        let mut leaders: BTreeSet<_> = entries.iter().copied().collect();
+        let mut work = entries.to_vec();
+
+        while let Some(address) = work.pop() {
+            if code.contains_key(&address) {
+                continue;
+            }
+            let instr = match program.get(address..).and_then(Instruction::decode) {
+                Some(instr) => instr,
+                None => {
+                    leaders.insert(address);
+                    continue;
+                }
+            };
+
+            let (edges, _) = flow(address, &instr);
+            for edge in &edges {
+                if ends_block(&instr) {
+                    leaders.insert(edge.target());
+                }
+                work.push(edge.target());
+            }
+            code.insert(address, instr);
+        }
+
+        let mut blocks = BTreeMap::new();
+        for &start in &leaders {
+            let mut block = Block {
+                start,
+                instructions: Vec::new(),
+                edges: Vec::new(),
+                indirect: false,
+                invalid: !code.contains_key(&start),
+            };
+
+            let mut address = start;
+            while let Some(instr) = code.get(&address) {
+                let (edges, indirect) = flow(address, instr);
+                block.instructions.push((address, instr.clone()));
+                address += instr.size();
+
+                if ends_block(instr) || leaders.contains(&address) {
+                    block.edges = edges;
+                    block.indirect = indirect;
+                    break;
+                }
+            }
+
+            blocks.insert(start, block);
+        }
+
+        Cfg { blocks }
+    }
+
+    // Addresses of the jumps with targets that are only known at runtime.
+    pub fn indirect_jumps(&self) -> Vec<usize> {
+        self.blocks
+            .values()
+            .filter(|b| b.indirect)
+            .filter_map(|b| b.instructions.last().map(|(a, _)| *a))
+            .collect()
+    }
+
+    // Renders the graph in Graphviz DOT format. Indirect jumps lead to a single node named
+    // "indirect", blocks at invalid instructions are drawn red.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph intcode {{").unwrap();
+        writeln!(dot, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for (address, instr) in &block.instructions {
+                write!(label, "{}: {}\\l", address, instr).unwrap();
+            }
+            if block.invalid {
+                write!(label, "{}: invalid\\l", block.start).unwrap();
+                writeln!(
+                    dot,
+                    "    b{} [label=\"{}\", color=red];",
+                    block.start, label
+                )
+                .unwrap();
+            } else {
+                writeln!(dot, "    b{} [label=\"{}\"];", block.start, label).unwrap();
+            }
+
+            for edge in &block.edges {
+                match edge {
+                    Edge::Fallthrough(t) => writeln!(dot, "    b{} -> b{};", block.start, t),
+                    Edge::Jump(t) => {
+                        writeln!(dot, "    b{} -> b{} [label=\"jump\"];", block.start, t)
+                    }
+                }
+                .unwrap();
+            }
+            if block.indirect {
+                writeln!(dot, "    b{} -> indirect [style=dashed];", block.start).unwrap();
+            }
+        }
+
+        if self.blocks.values().any(|b| b.indirect) {
+            writeln!(dot, "    indirect [shape=diamond];").unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+}
@@ -0,0 +1,168 @@
+// Command line handling shared by the binaries that run or analyze Intcode programs. All of them
+// accept any number of `--patch <addr>=<value>` options, which overwrite memory cells of the
+// program after it is loaded, the way day 2 sets noun and verb. `--record <file>` records the
+// values all machines of the binary read and write to the file, and `--replay <file>` replays such
+// a recording, see recording. The binaries create their machines with Args::machine, which
+// attaches the tap for these options.
+
+use crate::recording::{Recorder, Replay, SharedTap};
+use crate::{Intcode, Io, StdIo};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn test_parse() {
+    let args = Args::parse(
+        [
+            "input.txt",
+            "--patch",
+            "1=12",
+            "--ascii",
+            "--patch",
+            "2=-2",
+            "--record",
+            "session.txt",
+        ]
+        .iter()
+        .map(|a| a.to_string()),
+    )
+    .unwrap();
+
+    assert_eq!(vec!["input.txt", "--ascii"], args.rest);
+    assert_eq!(vec![(1, 12), (2, -2)], args.patches);
+    assert_eq!(Some("session.txt".to_string()), args.record);
+    assert_eq!(None, args.replay);
+
+    let mut program = vec![1, 0, 0];
+    args.patch(&mut program);
+    assert_eq!(vec![1, 12, -2], program);
+
+    assert!(Args::parse(vec!["--patch".to_string()]).is_err());
+    assert!(Args::parse(vec!["--patch".to_string(), "-1=0".to_string()]).is_err());
+    assert!(Args::parse(vec!["--replay".to_string()]).is_err());
+}
+
+#[derive(Default)]
+pub struct Args {
+    // The arguments besides the program name and the patches.
+    pub rest: Vec<String>,
+    pub patches: Vec<(usize, isize)>,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    // The tap for --record or --replay, opened by from_env.
+    pub tap: Option<SharedTap>,
+}
+
+// Parses `<addr>=<value>`.
+pub fn parse_patch(patch: &str) -> Option<(usize, isize)> {
+    let mut parts = patch.splitn(2, '=');
+    let address = parts.next()?.trim().parse().ok()?;
+    let value = parts.next()?.trim().parse().ok()?;
+    Some((address, value))
+}
+
+impl Args {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+        let mut args = args.into_iter();
+        let mut rest = Vec::new();
+        let mut patches = Vec::new();
+        let mut record = None;
+        let mut replay = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--patch" => {
+                    let patch = args
+                        .next()
+                        .ok_or_else(|| "Missing <addr>=<value> after --patch".to_string())?;
+                    patches.push(
+                        parse_patch(&patch).ok_or_else(|| format!("Invalid patch '{}'", patch))?,
+                    );
+                }
+                "--record" | "--replay" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| format!("Missing <file> after {}", arg))?;
+                    if arg == "--record" {
+                        record = Some(path);
+                    } else {
+                        replay = Some(path);
+                    }
+                }
+                _ => rest.push(arg),
+            }
+        }
+
+        if record.is_some() && replay.is_some() {
+            return Err("Only one of --record and --replay can be given".to_string());
+        }
+
+        Ok(Args {
+            rest,
+            patches,
+            record,
+            replay,
+            tap: None,
+        })
+    }
+
+    // Parses the command line and opens the tap for --record or --replay.
+    pub fn from_env() -> Args {
+        let mut args = Args::parse(std::env::args().skip(1)).unwrap_or_else(|e| panic!("{}", e));
+        args.tap = args.open_tap().unwrap_or_else(|e| panic!("{}", e));
+        args
+    }
+
+    // A tap recording to or replaying from the given file, None without either option.
+    pub fn open_tap(&self) -> Result<Option<SharedTap>, String> {
+        let tap: SharedTap = if let Some(path) = &self.record {
+            let file = std::fs::File::create(path)
+                .map_err(|e| format!("Could not create {}: {}", path, e))?;
+            Arc::new(Mutex::new(Recorder::new(std::io::LineWriter::new(file))))
+        } else if let Some(path) = &self.replay {
+            let recording = std::fs::read_to_string(path)
+                .map_err(|e| format!("Could not read {}: {}", path, e))?;
+            let replay = Replay::parse(&recording).map_err(|e| format!("{}: {}", path, e))?;
+            Arc::new(Mutex::new(replay))
+        } else {
+            return Ok(None);
+        };
+
+        Ok(Some(tap))
+    }
+
+    // A machine running the program, attached to the tap if there is one.
+    pub fn machine(&self, program: Vec<isize>) -> Intcode {
+        let mut interpreter = Intcode::new(program);
+        if let Some(tap) = &self.tap {
+            interpreter.attach_tap(tap.clone());
+        }
+        interpreter
+    }
+
+    // Like intcode::evaluate_io, on a machine created with machine.
+    pub fn evaluate_io(&self, program: Vec<isize>, io: &mut dyn Io) -> isize {
+        self.machine(program)
+            .evaluate(io)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn evaluate(&self, program: Vec<isize>) -> isize {
+        self.evaluate_io(program, &mut StdIo::new())
+    }
+
+    // Applies the patches, extending the program with zeros if a patch lies beyond its end.
+    pub fn patch(&self, program: &mut Vec<isize>) {
+        for &(address, value) in &self.patches {
+            if program.len() <= address {
+                program.resize(address + 1, 0);
+            }
+            program[address] = value;
+        }
+    }
+
+    pub fn read_program(&self, path: &str) -> Vec<isize> {
+        let mut program = crate::read_intcode_file(path);
+        self.patch(&mut program);
+        program
+    }
+}
@@ -0,0 +1,192 @@
+use crate::disasm::{self, Instruction, Item, Line};
+use crate::trace::{TraceRecord, Tracer};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+#[test]
+fn test_coverage() {
+    // Outputs 1 for a positive input, the else branch is not taken for 5.
+    let program = crate::asm::assemble(
+        "
+                IN [n]
+                LT #0, [n], [c]
+                JNZ [c], #pos
+                OUT #0
+                HLT
+        pos:    OUT #1
+                HLT
+        n:      .data 0
+        c:      .data 0
+        unused: .data 42
+        ",
+    )
+    .unwrap();
+
+    let mut coverage = Coverage::new();
+    let mut interpreter = crate::Intcode::new(program.clone());
+    let mut io = crate::BufIo::new(&[5]);
+    interpreter.run_traced(&mut io, &mut coverage).unwrap();
+
+    assert!(coverage.is_executed(0) && coverage.is_executed(2) && coverage.is_executed(12));
+    assert!(!coverage.is_executed(9) && !coverage.is_executed(11));
+    assert!(coverage.is_read(15) && coverage.is_written(15) && coverage.is_written(16));
+    assert!(!coverage.is_read(17) && !coverage.is_written(17));
+    assert_eq!((5, 7), coverage.instructions(&program));
+
+    let annotated = coverage.annotate(&program);
+    let lines: Vec<_> = annotated.lines().collect();
+    assert_eq!(10, lines.len());
+    assert!(lines[0].starts_with("x..     0: 3,15"));
+    assert!(lines[3].starts_with("...     9: 104,0"));
+    assert!(lines[7].starts_with(".rw    15: 0"));
+    assert!(lines[9].starts_with("...    17: 42"));
+
+    assert!(coverage
+        .summary(&program)
+        .starts_with("Instructions executed: 5 of 7 (71.43%)\n"));
+}
+
+// Collects the addresses that were executed as instructions, read as data and written. Cells
+// read as parameters of an instruction count as executed, not as read.
+#[derive(Default)]
+pub struct Coverage {
+    // Addresses of executed instructions.
+    instructions: BTreeSet<usize>,
+    executed: BTreeSet<usize>,
+    read: BTreeSet<usize>,
+    written: BTreeSet<usize>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    // Whether an instruction started at the address.
+    pub fn is_executed(&self, address: usize) -> bool {
+        self.instructions.contains(&address)
+    }
+
+    pub fn is_read(&self, address: usize) -> bool {
+        self.read.contains(&address)
+    }
+
+    pub fn is_written(&self, address: usize) -> bool {
+        self.written.contains(&address)
+    }
+
+    // Disassembles the program like disasm::disassemble, but starts instructions at every
+    // executed address, so that data decoded as code does not hide executed instructions.
+    pub fn disassemble(&self, program: &[isize]) -> Vec<Line> {
+        let mut lines = Vec::new();
+        let mut address = 0;
+
+        while address < program.len() {
+            let item = match Instruction::decode(&program[address..]) {
+                Some(instr)
+                    if self
+                        .instructions
+                        .range(address + 1..address + instr.size())
+                        .next()
+                        .is_none() =>
+                {
+                    Item::Code(instr)
+                }
+                _ => Item::Data(program[address]),
+            };
+            let line = Line { address, item };
+            address += line.size();
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    // Number of executed instructions and of all instructions in the disassembly.
+    pub fn instructions(&self, program: &[isize]) -> (usize, usize) {
+        let code: Vec<_> = self
+            .disassemble(program)
+            .into_iter()
+            .filter(|line| matches!(line.item, Item::Code(_)))
+            .collect();
+        let executed = code
+            .iter()
+            .filter(|line| self.is_executed(line.address))
+            .count();
+        (executed, code.len())
+    }
+
+    // The disassembly with flags for lines that were executed (x), read (r) or written (w).
+    pub fn annotate(&self, program: &[isize]) -> String {
+        let mut result = String::new();
+        for line in self.disassemble(program) {
+            let cells = line.address..line.address + line.size();
+            let flag = |set: &BTreeSet<usize>, c| {
+                if set.range(cells.clone()).next().is_some() {
+                    c
+                } else {
+                    '.'
+                }
+            };
+            writeln!(
+                result,
+                "{}{}{} {}",
+                flag(&self.executed, 'x'),
+                flag(&self.read, 'r'),
+                flag(&self.written, 'w'),
+                disasm::format_line(program, &line)
+            )
+            .unwrap();
+        }
+        result
+    }
+
+    pub fn summary(&self, program: &[isize]) -> String {
+        let (executed, total) = self.instructions(program);
+        let percent = 100.0 * executed as f64 / std::cmp::max(total, 1) as f64;
+        let within = |set: &BTreeSet<usize>| set.range(..program.len()).count();
+        let beyond = |set: &BTreeSet<usize>| set.range(program.len()..).count();
+
+        let mut summary = String::new();
+        writeln!(
+            summary,
+            "Instructions executed: {} of {} ({:.2}%)",
+            executed, total, percent
+        )
+        .unwrap();
+        writeln!(
+            summary,
+            "Cells executed: {}, read: {}, written: {} of {}",
+            within(&self.executed),
+            within(&self.read),
+            within(&self.written),
+            program.len()
+        )
+        .unwrap();
+        writeln!(
+            summary,
+            "Cells beyond the program read: {}, written: {}",
+            beyond(&self.read),
+            beyond(&self.written)
+        )
+        .unwrap();
+        summary
+    }
+}
+
+impl Tracer for Coverage {
+    fn trace(&mut self, record: &TraceRecord) {
+        self.instructions.insert(record.address);
+        let size = 1 + record.operands.len();
+        self.executed.extend(record.address..record.address + size);
+
+        for operand in &record.operands {
+            if let (Some(address), Some(_)) = (operand.address, operand.value) {
+                self.read.insert(address);
+            }
+        }
+        if let Some((address, _)) = record.write {
+            self.written.insert(address);
+        }
+    }
+}
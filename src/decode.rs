@@ -0,0 +1,142 @@
+use crate::{Cell, ErrorKind};
+
+#[test]
+fn test_invalidate() {
+    let decoded: Decoded<isize> = Decoded {
+        code: 1,
+        operands: [
+            Operand::Position(1),
+            Operand::Position(2),
+            Operand::Position(3),
+        ],
+        size: 4,
+    };
+    let mut cache = DecodeCache::new(0);
+    cache.insert(10, decoded);
+    cache.insert(14, decoded);
+
+    cache.invalidate(9);
+    cache.invalidate(14 + 4);
+    assert!(cache.get(10).is_some() && cache.get(14).is_some());
+
+    cache.invalidate(13);
+    assert!(cache.get(10).is_none());
+    assert!(cache.get(14).is_some());
+}
+
+// Cached instructions are only kept for addresses below this limit, code beyond it is decoded on
+// every execution.
+const CACHE_LIMIT: usize = 1 << 16;
+
+// A parameter with its mode applied, as far as that is possible without the relative base.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Operand<C> {
+    Position(usize),
+    Immediate(C),
+    Relative(isize),
+    // Reading the parameter cell or applying the mode failed, the error is only reported if the
+    // instruction uses the parameter.
+    Invalid(ErrorKind),
+}
+
+impl<C: Cell> Operand<C> {
+    pub fn new(param: Result<C, ErrorKind>, mode: u8) -> Operand<C> {
+        let value = match param {
+            Ok(value) => value,
+            Err(e) => return Operand::Invalid(e),
+        };
+
+        match (mode, value.to_isize()) {
+            (0, Some(address)) if address < 0 => {
+                Operand::Invalid(ErrorKind::NegativeAddress(address))
+            }
+            (0, Some(address)) => Operand::Position(address as usize),
+            (1, _) => Operand::Immediate(value),
+            (2, Some(offset)) => Operand::Relative(offset),
+            (0, None) | (2, None) => Operand::Invalid(ErrorKind::Overflow),
+            (m, _) => Operand::Invalid(ErrorKind::InvalidMode(m)),
+        }
+    }
+}
+
+// An instruction with the opcode without its modes and the operands.
+#[derive(Clone, Copy)]
+pub(crate) struct Decoded<C> {
+    pub code: u8,
+    pub operands: [Operand<C>; 3],
+    pub size: usize,
+}
+
+// Decoded instructions by address. Entries have to be invalidated whenever a cell they cover
+// is written.
+#[derive(Clone)]
+pub(crate) struct DecodeCache<C> {
+    enabled: bool,
+    entries: Vec<Option<Decoded<C>>>,
+    // Whether a cell belongs to a cached instruction, so that writes to data do not have to look
+    // for instructions to drop. Flags stay set when the instruction is dropped.
+    code: Vec<bool>,
+}
+
+impl<C: Cell> DecodeCache<C> {
+    // The cache is allocated for code up to the given address up front, it grows beyond that
+    // as needed.
+    pub fn new(size: usize) -> DecodeCache<C> {
+        let size = std::cmp::min(size, CACHE_LIMIT);
+        DecodeCache {
+            enabled: true,
+            entries: vec![None; size],
+            code: vec![false; size],
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.code.clear();
+    }
+
+    pub fn get(&self, address: usize) -> Option<&Decoded<C>> {
+        self.entries.get(address)?.as_ref()
+    }
+
+    pub fn insert(&mut self, address: usize, decoded: Decoded<C>) {
+        if !self.enabled || address >= CACHE_LIMIT {
+            return;
+        }
+        if self.entries.len() <= address {
+            self.entries.resize_with(address + 1, || None);
+        }
+        self.entries[address] = Some(decoded);
+
+        let end = address + decoded.size;
+        if self.code.len() < end {
+            self.code.resize(end, false);
+        }
+        self.code[address..end].fill(true);
+    }
+
+    // Drops all instructions covering the given address.
+    pub fn invalidate(&mut self, address: usize) {
+        if self.code.get(address) != Some(&true) {
+            return;
+        }
+
+        let first = address.saturating_sub(3);
+        for start in first..=address {
+            if let Some(entry) = self.entries.get_mut(start) {
+                if entry.is_some_and(|d| start + d.size > address) {
+                    *entry = None;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,510 @@
+// Decompiles an Intcode program into structured pseudo-code.
+//
+// Functions are recognized by the usual calling convention: the caller stores the return
+// address (an immediate that equals the address after the jump) into the callee's frame and
+// jumps to the function with an unconditional immediate jump; the callee returns with an
+// unconditional jump through a relative parameter. Code reachable from address 0 forms main.
+//
+// Within a function, jumps back to an earlier instruction become loops, conditional jumps
+// forward become if/else. Jumps that do not fit this structure are kept as goto with a label.
+
+use crate::disasm::{Instruction, Op, Param};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fmt::Write;
+
+#[test]
+fn test_decompile() {
+    let program = crate::asm::assemble(
+        "
+                ARB #100
+        loop:   IN [n]
+                JZ [n], #done
+                ADD [n], #0, [rb+1]
+                ADD #back, #0, [rb+0]
+                JZ #0, #abs
+        back:   OUT [rb+1]
+                JNZ #1, #loop
+        done:   HLT
+        abs:    LT [rb+1], #0, [rb+2]
+                JZ [rb+2], #pos
+                MUL [rb+1], #-1, [rb+1]
+                JNZ #1, #end
+        pos:    ADD [rb+1], #0, [rb+3]
+        end:    JZ #0, [rb+0]
+        n:      .data 0
+        ",
+    )
+    .unwrap();
+
+    assert_eq!(
+        "fn main() {
+    rb += 100                                   // 0
+    loop {                                      // 2
+        m[45] = input()                         // 2
+        if (m[45] == 0) break                   // 4
+        rb[1] = m[45]                           // 7
+        call f_24                               // 15
+        output(rb[1])                           // 18
+    }
+    halt                                        // 23
+}
+
+fn f_24() {
+    rb[2] = rb[1] < 0                           // 24
+    if (rb[2] != 0) {                           // 28
+        rb[1] *= -1                             // 31
+    } else {
+        rb[3] = rb[1]                           // 38
+    }
+    return                                      // 42
+}
+",
+        decompile(&program)
+    );
+}
+
+#[test]
+fn test_goto() {
+    // The jump into the middle of the loop does not fit the structure.
+    let program = crate::asm::assemble(
+        "
+                JNZ [x], #inner
+        loop:   OUT [x]
+        inner:  ADD [x], #1, [x]
+                JNZ [x], #loop
+                HLT
+        x:      .data 5
+        ",
+    )
+    .unwrap();
+
+    let code = decompile(&program);
+    assert!(code.contains("if (m[13] != 0) goto L_5"));
+    assert!(code.contains("L_5:\n        m[13] += 1"));
+}
+
+#[test]
+fn test_dropped_labels() {
+    // Jumps into the loop at the setup of a call, and to a jump to the next instruction.
+    let program = crate::asm::assemble(
+        "
+                JNZ [x], #inner
+                JNZ [y], #skip
+        loop:   OUT [x]
+        inner:  ADD #back, #0, [rb+0]
+                JZ #0, #f
+        back:   ADD [x], #-1, [x]
+                JNZ [x], #loop
+        skip:   JZ #0, #end
+        end:    HLT
+        f:      JZ #0, [rb+0]
+        x:      .data 5
+        y:      .data 0
+        ",
+    )
+    .unwrap();
+
+    let code = decompile(&program);
+    assert!(code.contains("goto L_8"));
+    assert!(code.contains("L_8:\n        call f_"));
+    assert!(code.contains("goto L_22"));
+    assert!(code.contains("L_22:\n    halt"));
+}
+
+// Column of the address comments.
+const COMMENT_COLUMN: usize = 48;
+
+#[derive(Clone)]
+struct Cond {
+    operand: String,
+    nonzero: bool,
+}
+
+impl Cond {
+    fn negate(&self) -> Cond {
+        Cond {
+            operand: self.operand.clone(),
+            nonzero: !self.nonzero,
+        }
+    }
+}
+
+impl fmt::Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = if self.nonzero { "!=" } else { "==" };
+        write!(f, "{} {} 0", self.operand, op)
+    }
+}
+
+#[derive(Clone)]
+enum Target {
+    Address(usize),
+    // Through a relative parameter, a return.
+    Relative,
+    Indirect(String),
+}
+
+#[derive(Clone)]
+enum Kind {
+    Plain(String),
+    Halt,
+    // Stores the return address of the following call.
+    CallSetup,
+    Call(usize),
+    // A jump that is taken if the condition holds, or always.
+    Jump(Option<Cond>, Target),
+}
+
+enum Stmt {
+    Plain(usize, String),
+    If(usize, Cond, Vec<Stmt>, Vec<Stmt>),
+    // A loop with its head, left at the end unless the condition holds.
+    Loop(usize, Vec<Stmt>, Option<Cond>),
+}
+
+fn operand(param: Param) -> String {
+    match param {
+        Param::Position(a) => format!("m[{}]", a),
+        Param::Immediate(v) => v.to_string(),
+        Param::Relative(o) => format!("rb[{}]", o),
+    }
+}
+
+fn assignment(instr: &Instruction) -> String {
+    let p = &instr.params;
+    let (a, b, t) = (operand(p[0]), operand(p[1]), operand(p[2]));
+    match (instr.op, p[1]) {
+        (Op::Add, Param::Immediate(0)) | (Op::Mul, Param::Immediate(1)) => format!("{} = {}", t, a),
+        (Op::Add, Param::Immediate(v)) if v < 0 && t == a => format!("{} -= {}", t, -(v as i128)),
+        (Op::Add, _) if t == a => format!("{} += {}", t, b),
+        (Op::Add, Param::Immediate(v)) if v < 0 => format!("{} = {} - {}", t, a, -(v as i128)),
+        (Op::Add, _) => format!("{} = {} + {}", t, a, b),
+        (Op::Mul, _) if t == a => format!("{} *= {}", t, b),
+        (Op::Mul, _) => format!("{} = {} * {}", t, a, b),
+        (Op::Lt, _) => format!("{} = {} < {}", t, a, b),
+        _ => format!("{} = {} == {}", t, a, b),
+    }
+}
+
+fn classify(address: usize, instr: &Instruction, previous: Option<&Instruction>) -> Kind {
+    let p = &instr.params;
+    match instr.op {
+        Op::Add | Op::Mul | Op::Lt | Op::Eq => Kind::Plain(assignment(instr)),
+        Op::In => Kind::Plain(format!("{} = input()", operand(p[0]))),
+        Op::Out => Kind::Plain(format!("output({})", operand(p[0]))),
+        Op::Arb => match p[0] {
+            Param::Immediate(v) if v < 0 => Kind::Plain(format!("rb -= {}", -(v as i128))),
+            _ => Kind::Plain(format!("rb += {}", operand(p[0]))),
+        },
+        Op::Hlt => Kind::Halt,
+        Op::Jnz | Op::Jz => {
+            let nonzero = instr.op == Op::Jnz;
+            let cond = match p[0] {
+                Param::Immediate(v) if (v != 0) == nonzero => None,
+                Param::Immediate(_) => return Kind::Plain("nop".to_string()),
+                c => Some(Cond {
+                    operand: operand(c),
+                    nonzero,
+                }),
+            };
+
+            let ret = (address + instr.size()) as isize;
+            let stores_ret = previous.is_some_and(|prev| {
+                matches!(prev.op, Op::Add | Op::Mul)
+                    && prev.params[..2].contains(&Param::Immediate(ret))
+            });
+
+            match p[1] {
+                Param::Immediate(t) if t >= 0 && cond.is_none() && stores_ret => {
+                    Kind::Call(t as usize)
+                }
+                Param::Immediate(t) if t >= 0 => Kind::Jump(cond, Target::Address(t as usize)),
+                Param::Relative(_) => Kind::Jump(cond, Target::Relative),
+                t => Kind::Jump(cond, Target::Indirect(operand(t))),
+            }
+        }
+    }
+}
+
+// Instructions of the function at entry, ordered by address. Adds the functions it calls to
+// calls.
+fn discover(
+    program: &[isize],
+    entry: usize,
+    calls: &mut BTreeSet<usize>,
+) -> Vec<(usize, Instruction, Kind)> {
+    let mut code: BTreeMap<usize, (Instruction, Kind)> = BTreeMap::new();
+    let mut work = vec![entry];
+
+    while let Some(address) = work.pop() {
+        if code.contains_key(&address) {
+            continue;
+        }
+        let instr = match program.get(address..).and_then(Instruction::decode) {
+            Some(instr) => instr,
+            None => continue,
+        };
+
+        let previous = code
+            .range(..address)
+            .next_back()
+            .filter(|(a, (i, _))| *a + i.size() == address)
+            .map(|(a, (i, _))| (*a, i.clone()));
+        let kind = classify(address, &instr, previous.as_ref().map(|(_, i)| i));
+        let next = address + instr.size();
+
+        match &kind {
+            Kind::Plain(_) | Kind::CallSetup => work.push(next),
+            Kind::Halt => (),
+            Kind::Call(f) => {
+                calls.insert(*f);
+                if let Some((a, _)) = previous {
+                    code.get_mut(&a).unwrap().1 = Kind::CallSetup;
+                }
+                work.push(next);
+            }
+            Kind::Jump(cond, target) => {
+                if let Target::Address(t) = target {
+                    work.push(*t);
+                }
+                if cond.is_some() {
+                    work.push(next);
+                }
+            }
+        }
+
+        code.insert(address, (instr, kind));
+    }
+
+    code.into_iter().map(|(a, (i, k))| (a, i, k)).collect()
+}
+
+struct Structurer<'a> {
+    code: &'a [(usize, Instruction, Kind)],
+    labels: BTreeSet<usize>,
+}
+
+// The innermost loop: its head and the address after it.
+type LoopContext = Option<(usize, usize)>;
+
+impl<'a> Structurer<'a> {
+    fn index_of(&self, address: usize) -> Option<usize> {
+        self.code.binary_search_by_key(&address, |c| c.0).ok()
+    }
+
+    // Structures the instructions with indices from..to. A loop headed at skip_loop is not
+    // detected again, that is the loop whose body is being structured.
+    fn structure(
+        &mut self,
+        from: usize,
+        to: usize,
+        context: LoopContext,
+        skip_loop: Option<usize>,
+    ) -> Vec<Stmt> {
+        let mut stmts = Vec::new();
+        let mut i = from;
+
+        while i < to {
+            let (address, instr, kind) = &self.code[i];
+            let address = *address;
+
+            if skip_loop != Some(i) {
+                let back = (i..to).rev().find(|&k| match &self.code[k].2 {
+                    Kind::Jump(_, Target::Address(t)) => *t == address,
+                    _ => false,
+                });
+                if let Some(k) = back {
+                    let (end, jump, kind) = &self.code[k];
+                    let exit = end + jump.size();
+                    let cond = match kind {
+                        Kind::Jump(cond, _) => cond.clone(),
+                        _ => None,
+                    };
+                    let body = self.structure(i, k, Some((address, exit)), Some(i));
+                    stmts.push(Stmt::Loop(address, body, cond));
+                    i = k + 1;
+                    continue;
+                }
+            }
+
+            let text = match kind {
+                Kind::Plain(text) => text.clone(),
+                Kind::Halt => "halt".to_string(),
+                Kind::CallSetup => {
+                    i += 1;
+                    continue;
+                }
+                Kind::Call(0) => "call main".to_string(),
+                Kind::Call(f) => format!("call f_{}", f),
+                // A jump to the next instruction has no effect.
+                Kind::Jump(None, Target::Address(t)) if *t == address + instr.size() => {
+                    i += 1;
+                    continue;
+                }
+                Kind::Jump(cond, target) => {
+                    let action = match target {
+                        Target::Address(t) if context.map(|c| c.1) == Some(*t) => {
+                            "break".to_string()
+                        }
+                        Target::Address(t) if context.map(|c| c.0) == Some(*t) => {
+                            "continue".to_string()
+                        }
+                        Target::Address(t) => {
+                            if let Some(stmt) = self.branch(i, to, *t, cond, context) {
+                                stmts.push(stmt.0);
+                                i = stmt.1;
+                                continue;
+                            }
+                            self.labels.insert(*t);
+                            format!("goto L_{}", t)
+                        }
+                        Target::Relative => "return".to_string(),
+                        Target::Indirect(t) => format!("goto *{}", t),
+                    };
+                    match cond {
+                        Some(cond) => format!("if ({}) {}", cond, action),
+                        None => action,
+                    }
+                }
+            };
+
+            stmts.push(Stmt::Plain(address, text));
+            i += 1;
+        }
+
+        stmts
+    }
+
+    // Turns a conditional forward jump at index i into if or if/else. Returns the statement and
+    // the index to continue at.
+    fn branch(
+        &mut self,
+        i: usize,
+        to: usize,
+        target: usize,
+        cond: &Option<Cond>,
+        context: LoopContext,
+    ) -> Option<(Stmt, usize)> {
+        let cond = cond.as_ref()?;
+        let address = self.code[i].0;
+        if target <= address {
+            return None;
+        }
+        let then_end = self.index_of(target).filter(|&t| t <= to)?;
+        if !self.single_entry(i, i + 1, then_end) {
+            return None;
+        }
+
+        // An unconditional jump at the end of the then branch over the else branch.
+        if then_end > i + 1 {
+            if let Kind::Jump(None, Target::Address(e)) = &self.code[then_end - 1].2 {
+                let else_end = self.index_of(*e).filter(|&e| e > then_end && e <= to);
+                if let Some(else_end) = else_end.filter(|&e| self.single_entry(i, then_end, e)) {
+                    let then = self.structure(i + 1, then_end - 1, context, None);
+                    let otherwise = self.structure(then_end, else_end, context, None);
+                    return Some((Stmt::If(address, cond.negate(), then, otherwise), else_end));
+                }
+            }
+        }
+
+        let then = self.structure(i + 1, then_end, context, None);
+        Some((Stmt::If(address, cond.negate(), then, Vec::new()), then_end))
+    }
+
+    // Whether no jump other than the one at index branch leads into the instructions with
+    // indices from..to.
+    fn single_entry(&self, branch: usize, from: usize, to: usize) -> bool {
+        self.code
+            .iter()
+            .enumerate()
+            .all(|(k, (_, _, kind))| match kind {
+                Kind::Jump(_, Target::Address(t)) if k != branch && (k < from || k >= to) => {
+                    !matches!(self.index_of(*t), Some(t) if t >= from && t < to)
+                }
+                _ => true,
+            })
+    }
+}
+
+fn line(out: &mut String, depth: usize, text: &str, address: Option<usize>) {
+    let text = format!("{}{}", "    ".repeat(depth), text);
+    match address {
+        Some(a) => writeln!(out, "{:<w$}// {}", text, a, w = COMMENT_COLUMN).unwrap(),
+        None => writeln!(out, "{}", text).unwrap(),
+    }
+}
+
+fn print(out: &mut String, stmts: &[Stmt], depth: usize, labels: &mut BTreeSet<usize>) {
+    for stmt in stmts {
+        let address = match stmt {
+            Stmt::Plain(a, _) | Stmt::If(a, ..) | Stmt::Loop(a, ..) => *a,
+        };
+        // Labels of statements that are not printed, like call setups, go to the next one.
+        let pending: Vec<_> = labels.range(..=address).copied().collect();
+        for label in pending {
+            labels.remove(&label);
+            line(out, depth - 1, &format!("L_{}:", label), None);
+        }
+
+        match stmt {
+            Stmt::Plain(a, text) => line(out, depth, text, Some(*a)),
+            Stmt::If(a, cond, then, otherwise) => {
+                line(out, depth, &format!("if ({}) {{", cond), Some(*a));
+                print(out, then, depth + 1, labels);
+                if !otherwise.is_empty() {
+                    line(out, depth, "} else {", None);
+                    print(out, otherwise, depth + 1, labels);
+                }
+                line(out, depth, "}", None);
+            }
+            Stmt::Loop(a, body, cond) => {
+                let open = if cond.is_some() { "do {" } else { "loop {" };
+                line(out, depth, open, Some(*a));
+                print(out, body, depth + 1, labels);
+                match cond {
+                    Some(cond) => line(out, depth, &format!("}} while ({})", cond), None),
+                    None => line(out, depth, "}", None),
+                }
+            }
+        }
+    }
+}
+
+pub fn decompile(program: &[isize]) -> String {
+    let mut functions = BTreeSet::new();
+    let mut pending = vec![0];
+    let mut bodies = BTreeMap::new();
+
+    while let Some(entry) = pending.pop() {
+        if !functions.insert(entry) {
+            continue;
+        }
+        let mut calls = BTreeSet::new();
+        bodies.insert(entry, discover(program, entry, &mut calls));
+        pending.extend(calls);
+    }
+
+    let mut out = String::new();
+    for (entry, code) in &bodies {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        match entry {
+            0 => out.push_str("fn main() {\n"),
+            _ => writeln!(out, "fn f_{}() {{", entry).unwrap(),
+        }
+
+        let mut structurer = Structurer {
+            code,
+            labels: BTreeSet::new(),
+        };
+        let stmts = structurer.structure(0, code.len(), None, None);
+        print(&mut out, &stmts, 1, &mut structurer.labels);
+        for label in &structurer.labels {
+            line(&mut out, 0, &format!("L_{}:", label), None);
+        }
+        out.push_str("}\n");
+    }
+    out
+}
@@ -0,0 +1,324 @@
+use std::fmt;
+
+#[test]
+fn test_decode() {
+    let instr = Instruction::decode(&[1002, 4, 3, 4]).unwrap();
+    assert_eq!(Op::Mul, instr.op);
+    assert_eq!(
+        vec![Param::Position(4), Param::Immediate(3), Param::Position(4)],
+        instr.params
+    );
+    assert_eq!("MUL [4], #3, [4]", instr.to_string());
+
+    let instr = Instruction::decode(&[204, -1]).unwrap();
+    assert_eq!("OUT [rb-1]", instr.to_string());
+    assert_eq!(
+        "ARB #7",
+        Instruction::decode(&[109, 7]).unwrap().to_string()
+    );
+
+    assert_eq!(None, Instruction::decode(&[11101, 1, 2, 3]));
+    assert_eq!(None, Instruction::decode(&[42]));
+    assert_eq!(None, Instruction::decode(&[1, 2]));
+    assert_eq!(None, Instruction::decode(&[304, 1]));
+    assert_eq!(
+        "OUT #7",
+        Instruction::decode(&[1104, 7]).unwrap().to_string()
+    );
+}
+
+#[test]
+fn test_disassemble() {
+    let lines = disassemble(&[3, 0, 4, 0, 99, 7]);
+    let items: Vec<_> = lines.iter().map(|l| (l.address, &l.item)).collect();
+
+    assert_eq!(
+        vec![
+            (0, &Item::Code(Instruction::decode(&[3, 0]).unwrap())),
+            (2, &Item::Code(Instruction::decode(&[4, 0]).unwrap())),
+            (4, &Item::Code(Instruction::decode(&[99]).unwrap())),
+            (5, &Item::Data(7)),
+        ],
+        items
+    );
+    assert_eq!(
+        "    0: 3,0                        IN [0]\n    \
+             2: 4,0                        OUT [0]\n    \
+             4: 99                         HLT\n    \
+             5: 7                          DATA 7\n",
+        listing(&[3, 0, 4, 0, 99, 7])
+    );
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum Op {
+    Add,
+    Mul,
+    In,
+    Out,
+    Jnz,
+    Jz,
+    Lt,
+    Eq,
+    Arb,
+    Hlt,
+}
+
+pub static OPS: [Op; 10] = [
+    Op::Add,
+    Op::Mul,
+    Op::In,
+    Op::Out,
+    Op::Jnz,
+    Op::Jz,
+    Op::Lt,
+    Op::Eq,
+    Op::Arb,
+    Op::Hlt,
+];
+
+impl Op {
+    pub fn from_code(code: isize) -> Option<Op> {
+        OPS.iter().copied().find(|op| op.code() == code)
+    }
+
+    pub fn code(self) -> isize {
+        match self {
+            Op::Add => 1,
+            Op::Mul => 2,
+            Op::In => 3,
+            Op::Out => 4,
+            Op::Jnz => 5,
+            Op::Jz => 6,
+            Op::Lt => 7,
+            Op::Eq => 8,
+            Op::Arb => 9,
+            Op::Hlt => 99,
+        }
+    }
+
+    pub fn arity(self) -> usize {
+        match self {
+            Op::Add | Op::Mul | Op::Lt | Op::Eq => 3,
+            Op::Jnz | Op::Jz => 2,
+            Op::In | Op::Out | Op::Arb => 1,
+            Op::Hlt => 0,
+        }
+    }
+
+    // Index of the parameter the instruction writes to, if any.
+    pub fn output(self) -> Option<usize> {
+        match self {
+            Op::Add | Op::Mul | Op::Lt | Op::Eq => Some(2),
+            Op::In => Some(0),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Op::Add => "ADD",
+            Op::Mul => "MUL",
+            Op::In => "IN",
+            Op::Out => "OUT",
+            Op::Jnz => "JNZ",
+            Op::Jz => "JZ",
+            Op::Lt => "LT",
+            Op::Eq => "EQ",
+            Op::Arb => "ARB",
+            Op::Hlt => "HLT",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Op> {
+        OPS.iter()
+            .copied()
+            .find(|op| op.name().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum Param {
+    Position(isize),
+    Immediate(isize),
+    Relative(isize),
+}
+
+impl Param {
+    fn decode(mode: isize, value: isize) -> Option<Param> {
+        match mode {
+            0 => Some(Param::Position(value)),
+            1 => Some(Param::Immediate(value)),
+            2 => Some(Param::Relative(value)),
+            _ => None,
+        }
+    }
+
+    pub fn mode(self) -> isize {
+        match self {
+            Param::Position(_) => 0,
+            Param::Immediate(_) => 1,
+            Param::Relative(_) => 2,
+        }
+    }
+
+    pub fn value(self) -> isize {
+        match self {
+            Param::Position(v) | Param::Immediate(v) | Param::Relative(v) => v,
+        }
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Param::Position(a) => write!(f, "[{}]", a),
+            Param::Immediate(v) => write!(f, "#{}", v),
+            Param::Relative(o) if o < 0 => write!(f, "[rb-{}]", -(o as i128)),
+            Param::Relative(o) => write!(f, "[rb+{}]", o),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Instruction {
+    pub op: Op,
+    pub params: Vec<Param>,
+}
+
+impl Instruction {
+    // Decodes the instruction at the start of the given cells. Returns None if the cells do not
+    // hold a valid instruction, including writes in immediate mode and truncated parameters. Mode
+    // digits beyond the parameters are ignored like the VM does, encode leaves them out.
+    pub fn decode(cells: &[isize]) -> Option<Instruction> {
+        let code = *cells.first()?;
+        if code < 0 {
+            return None;
+        }
+
+        let op = Op::from_code(code % 100)?;
+        if cells.len() <= op.arity() {
+            return None;
+        }
+
+        let mut modes = code / 100;
+        let mut params = Vec::with_capacity(op.arity());
+        for value in &cells[1..=op.arity()] {
+            params.push(Param::decode(modes % 10, *value)?);
+            modes /= 10;
+        }
+
+        if let Some(Param::Immediate(_)) = op.output().map(|i| params[i]) {
+            return None;
+        }
+
+        Some(Instruction { op, params })
+    }
+
+    pub fn size(&self) -> usize {
+        self.params.len() + 1
+    }
+
+    pub fn encode(&self) -> Vec<isize> {
+        let mut code = self.op.code();
+        let mut factor = 100;
+        for p in &self.params {
+            code += p.mode() * factor;
+            factor *= 10;
+        }
+
+        let mut cells = vec![code];
+        cells.extend(self.params.iter().map(|p| p.value()));
+        cells
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.op.name())?;
+        for (i, p) in self.params.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, p)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Item {
+    Code(Instruction),
+    Data(isize),
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Item::Code(instr) => write!(f, "{}", instr),
+            Item::Data(value) => write!(f, "DATA {}", value),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Line {
+    pub address: usize,
+    pub item: Item,
+}
+
+impl Line {
+    pub fn size(&self) -> usize {
+        match &self.item {
+            Item::Code(instr) => instr.size(),
+            Item::Data(_) => 1,
+        }
+    }
+}
+
+// Linear sweep over the program. Cells that do not decode are emitted as data.
+pub fn disassemble(program: &[isize]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut address = 0;
+
+    while address < program.len() {
+        let item = match Instruction::decode(&program[address..]) {
+            Some(instr) => Item::Code(instr),
+            None => Item::Data(program[address]),
+        };
+        let line = Line { address, item };
+        address += line.size();
+        lines.push(line);
+    }
+
+    lines
+}
+
+pub fn format_line(program: &[isize], line: &Line) -> String {
+    let raw = program[line.address..(line.address + line.size())]
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!("{:>5}: {:<26} {}", line.address, raw, line.item)
+}
+
+pub fn listing(program: &[isize]) -> String {
+    let mut result = String::new();
+    for line in disassemble(program) {
+        result.push_str(&format_line(program, &line));
+        result.push('\n');
+    }
+    result
+}
+
+// Renders the program as assembler source, one instruction or data cell per line.
+pub fn source(program: &[isize]) -> String {
+    let mut result = String::new();
+    for line in disassemble(program) {
+        result.push_str(&format!(
+            "    {:<32} ; {}\n",
+            line.item.to_string(),
+            line.address
+        ));
+    }
+    result
+}
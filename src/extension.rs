@@ -0,0 +1,173 @@
+// Extra opcodes for experimental instruction sets. An extension declares its parameters, which
+// use the usual parameter modes, and is called with the values of the parameters it reads. It
+// may write at most one parameter, so that tracing and the history can record the write like the
+// one of a builtin instruction.
+
+use crate::{Cell, ErrorKind, Intcode};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn test_extension() {
+    // RND [10]; PRN [10]; PRN #7; HLT
+    let program = vec![50, 10, 51, 10, 151, 7, 99, 0, 0, 0, 0];
+
+    let mut interpreter = Intcode::new(program.clone());
+    assert_eq!(
+        ErrorKind::InvalidOpcode,
+        interpreter.try_step(0).unwrap_err().kind
+    );
+
+    let printer = Arc::new(Mutex::new(DebugPrint::new(Vec::new())));
+    let mut interpreter = Intcode::new(program);
+    interpreter.register(50, Arc::new(Mutex::new(Random::new(1))));
+    interpreter.register(51, printer.clone());
+    assert_eq!(crate::State::Terminated, interpreter.step(0));
+
+    let random = Random::new(1).next_value();
+    assert_eq!(Ok(random), interpreter.peek(10));
+    let printed = String::from_utf8(printer.lock().unwrap().writer.clone()).unwrap();
+    assert_eq!(format!("{}\n7\n", random), printed);
+    assert_eq!(4, interpreter.instructions_retired());
+
+    // Seeds that differ in the top bit only give different numbers.
+    let mut high = Random::new(1 | 1 << 63);
+    assert_ne!(random, high.next_value());
+}
+
+#[test]
+fn test_modes() {
+    // Stores the sum of its first two parameters to the third one.
+    struct Add;
+
+    impl Extension for Add {
+        fn params(&self) -> Vec<Param> {
+            vec![Param::Read, Param::Read, Param::Write]
+        }
+
+        fn execute(&mut self, values: &[isize]) -> Result<Option<isize>, ErrorKind> {
+            Ok(Some(values[0] + values[1]))
+        }
+    }
+
+    // ARB #5; ADD2 [rb+1], #3, [rb+2]; ADD2 #1, #1, #0 with its first parameter overwritten
+    let program = vec![109, 5, 21242, 1, 3, 2, 11142, 1, 1, 0, 99, 0];
+    let mut interpreter = Intcode::new(program);
+    interpreter.register(42, Arc::new(Mutex::new(Add)));
+
+    let error = interpreter.try_step(0).unwrap_err();
+    assert_eq!((ErrorKind::ImmediateStore, 6), (error.kind, error.iptr));
+    assert_eq!(Ok(11145), interpreter.peek(7));
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Param {
+    // The value of the parameter is passed to the extension.
+    Read,
+    // The value returned by the extension is stored to the parameter.
+    Write,
+}
+
+pub trait Extension<C = isize>: Send {
+    // At most three parameters, at most one of them written.
+    fn params(&self) -> Vec<Param>;
+
+    // Called with the values of the read parameters. Returns the value stored to the written
+    // parameter, None leaves it unchanged.
+    fn execute(&mut self, values: &[C]) -> Result<Option<C>, ErrorKind>;
+}
+
+pub type SharedExtension<C = isize> = Arc<Mutex<dyn Extension<C>>>;
+
+// An extension registered for an opcode, with its parameters. Machines keep it behind an Arc, so
+// executing it does not copy the parameters.
+pub(crate) struct Registered<C> {
+    pub params: Vec<Param>,
+    pub extension: SharedExtension<C>,
+}
+
+impl<C: Cell> Intcode<C> {
+    // Executes the extension for instructions with the opcode. Panics if the opcode is one of the
+    // builtin ones or the extension has too many parameters or writes more than one. Clones of the
+    // machine share the extension.
+    pub fn register(&mut self, opcode: u8, extension: SharedExtension<C>) {
+        assert!(
+            (10..99).contains(&opcode),
+            "Opcode {} is not available for extensions",
+            opcode
+        );
+        let params = extension.lock().unwrap().params();
+        assert!(
+            params.len() <= 3,
+            "Extensions have at most three parameters"
+        );
+        assert!(
+            params.iter().filter(|p| **p == Param::Write).count() <= 1,
+            "Extensions write at most one parameter"
+        );
+
+        self.extensions
+            .insert(opcode, Arc::new(Registered { params, extension }));
+        self.cache.clear();
+    }
+}
+
+// Prints the value of its parameter.
+pub struct DebugPrint<W> {
+    writer: W,
+}
+
+impl<W: Write> DebugPrint<W> {
+    pub fn new(writer: W) -> DebugPrint<W> {
+        DebugPrint { writer }
+    }
+}
+
+impl<C: Cell, W: Write + Send> Extension<C> for DebugPrint<W> {
+    fn params(&self) -> Vec<Param> {
+        vec![Param::Read]
+    }
+
+    fn execute(&mut self, values: &[C]) -> Result<Option<C>, ErrorKind> {
+        // Debug output is best effort.
+        let _ = writeln!(self.writer, "{}", values[0]);
+        Ok(None)
+    }
+}
+
+// Stores a pseudo-random non-negative number to its parameter (xorshift64*).
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        // Spreads the seed with the finalizer of splitmix64, which maps different seeds to
+        // different states. The state of xorshift must not be zero, the one seed mapped to zero
+        // shares its state with another one.
+        let mut state = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        state ^= state >> 31;
+        Random {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    pub fn next_value(&mut self) -> isize {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 33) as isize
+    }
+}
+
+impl<C: Cell> Extension<C> for Random {
+    fn params(&self) -> Vec<Param> {
+        vec![Param::Write]
+    }
+
+    fn execute(&mut self, _values: &[C]) -> Result<Option<C>, ErrorKind> {
+        Ok(Some(C::from_isize(self.next_value())))
+    }
+}
@@ -0,0 +1,194 @@
+// Groups the outputs of a machine into records, for programs that talk in multi-value messages
+// like the painting robot (color, turn), the arcade (x, y, tile) or the network (address, x, y).
+// Records either have a fixed number of values and are returned as tuples, or end with a
+// delimiter.
+
+use crate::{Intcode, IntcodeError, State};
+use std::fmt;
+use std::marker::PhantomData;
+
+#[test]
+fn test_tuples() {
+    // Outputs each input twice, as a pair.
+    let program = vec![3, 9, 4, 9, 4, 9, 1105, 1, 0, 0];
+    let mut framed = tuples::<(isize, isize)>(Intcode::new(program));
+
+    assert_eq!(Ok(State::Input), framed.try_step(0));
+    assert_eq!(Ok(State::Output((5, 5))), framed.try_step(5));
+    assert_eq!(Ok(State::Input), framed.try_step(0));
+    assert_eq!(Ok(State::Output((-1, -1))), framed.try_step(-1));
+}
+
+#[test]
+fn test_delimited() {
+    let program = vec![104, 72, 104, 105, 104, 10, 104, 10, 99];
+    let mut framed = delimited(Intcode::new(program), 10);
+
+    assert_eq!(Ok(State::Output(vec![72, 105])), framed.try_step(0));
+    assert_eq!(Ok(State::Output(vec![])), framed.try_step(0));
+    assert_eq!(Ok(State::Terminated), framed.try_step(0));
+}
+
+#[test]
+fn test_partial() {
+    let program = vec![104, 1, 104, 2, 104, 3, 104, 4, 99];
+    let mut framed = tuples::<(isize, isize, isize)>(Intcode::new(program));
+
+    assert_eq!(Ok(State::Output((1, 2, 3))), framed.try_step(0));
+    assert_eq!(Err(FramingError::Partial(vec![4])), framed.try_step(0));
+}
+
+// Records made of a fixed number of values.
+pub trait Tuple: Sized {
+    const ARITY: usize;
+
+    // Builds the record from exactly ARITY values.
+    fn from_values(values: &[isize]) -> Self;
+}
+
+impl Tuple for (isize, isize) {
+    const ARITY: usize = 2;
+
+    fn from_values(values: &[isize]) -> Self {
+        (values[0], values[1])
+    }
+}
+
+impl Tuple for (isize, isize, isize) {
+    const ARITY: usize = 3;
+
+    fn from_values(values: &[isize]) -> Self {
+        (values[0], values[1], values[2])
+    }
+}
+
+impl Tuple for (isize, isize, isize, isize) {
+    const ARITY: usize = 4;
+
+    fn from_values(values: &[isize]) -> Self {
+        (values[0], values[1], values[2], values[3])
+    }
+}
+
+pub trait Framing {
+    type Record;
+
+    // The record made of the outputs collected so far, None while it is incomplete.
+    fn record(&self, outputs: &[isize]) -> Option<Self::Record>;
+}
+
+pub struct Tuples<T>(PhantomData<T>);
+
+impl<T: Tuple> Framing for Tuples<T> {
+    type Record = T;
+
+    fn record(&self, outputs: &[isize]) -> Option<T> {
+        if outputs.len() == T::ARITY {
+            Some(T::from_values(outputs))
+        } else {
+            None
+        }
+    }
+}
+
+// Records ending with the delimiter, which is not part of the record.
+pub struct Delimited(pub isize);
+
+impl Framing for Delimited {
+    type Record = Vec<isize>;
+
+    fn record(&self, outputs: &[isize]) -> Option<Vec<isize>> {
+        match outputs.split_last() {
+            Some((last, record)) if *last == self.0 => Some(record.to_vec()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum FramingError {
+    Intcode(IntcodeError),
+    // The program halted with the values of an incomplete record pending.
+    Partial(Vec<isize>),
+}
+
+impl fmt::Display for FramingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FramingError::Intcode(e) => write!(f, "{}", e),
+            FramingError::Partial(values) => {
+                write!(f, "Program halted within a record after {:?}", values)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FramingError {}
+
+impl From<IntcodeError> for FramingError {
+    fn from(e: IntcodeError) -> FramingError {
+        FramingError::Intcode(e)
+    }
+}
+
+pub struct Framed<F> {
+    interpreter: Intcode,
+    framing: F,
+    pending: Vec<isize>,
+}
+
+pub fn tuples<T: Tuple>(interpreter: Intcode) -> Framed<Tuples<T>> {
+    Framed::new(interpreter, Tuples(PhantomData))
+}
+
+pub fn delimited(interpreter: Intcode, delimiter: isize) -> Framed<Delimited> {
+    Framed::new(interpreter, Delimited(delimiter))
+}
+
+impl<F: Framing> Framed<F> {
+    pub fn new(interpreter: Intcode, framing: F) -> Framed<F> {
+        Framed {
+            interpreter,
+            framing,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn interpreter(&self) -> &Intcode {
+        &self.interpreter
+    }
+
+    pub fn interpreter_mut(&mut self) -> &mut Intcode {
+        &mut self.interpreter
+    }
+
+    // Values of the record that is not complete yet.
+    pub fn pending(&self) -> &[isize] {
+        &self.pending
+    }
+
+    pub fn step(&mut self, input: isize) -> State<F::Record> {
+        self.try_step(input).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // Runs until the program requests input, completes a record or halts. Like Intcode::step,
+    // the input is consumed by the call following the one that returned State::Input.
+    pub fn try_step(&mut self, input: isize) -> Result<State<F::Record>, FramingError> {
+        loop {
+            match self.interpreter.try_step(input)? {
+                State::Input => return Ok(State::Input),
+                State::Output(o) => {
+                    self.pending.push(o);
+                    if let Some(record) = self.framing.record(&self.pending) {
+                        self.pending.clear();
+                        return Ok(State::Output(record));
+                    }
+                }
+                State::Terminated if self.pending.is_empty() => return Ok(State::Terminated),
+                State::Terminated => {
+                    return Err(FramingError::Partial(std::mem::take(&mut self.pending)))
+                }
+            }
+        }
+    }
+}
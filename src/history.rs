@@ -0,0 +1,52 @@
+use std::collections::VecDeque;
+
+// State before an executed instruction, enough to undo it.
+#[derive(Clone)]
+pub(crate) struct Undo<C> {
+    pub iptr: usize,
+    pub base: isize,
+    // The overwritten cell and its previous value.
+    pub write: Option<(usize, C)>,
+    // The instruction consumed an input.
+    pub input: bool,
+}
+
+// Undo log of the most recently executed instructions, holding at most limit entries.
+#[derive(Clone)]
+pub(crate) struct History<C> {
+    limit: usize,
+    entries: VecDeque<Undo<C>>,
+}
+
+impl<C> History<C> {
+    pub fn new(limit: usize) -> History<C> {
+        History {
+            limit,
+            entries: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn push(&mut self, undo: Undo<C>) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.entries.len() == self.limit {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(undo);
+    }
+
+    pub fn pop(&mut self) -> Option<Undo<C>> {
+        self.entries.pop_back()
+    }
+
+    // Number of entries that have to be undone to get back to before the last input.
+    pub fn since_input(&self) -> Option<usize> {
+        let position = self.entries.iter().rposition(|u| u.input)?;
+        Some(self.entries.len() - position)
+    }
+}
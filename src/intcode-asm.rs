@@ -0,0 +1,28 @@
+use std::io::Read;
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .expect("At least one command line argument is required.");
+
+    let mut source = String::new();
+    std::fs::File::open(&path)
+        .expect("Could not open input file")
+        .read_to_string(&mut source)
+        .expect("Error while reading from file.");
+
+    match intcode::asm::assemble(&source) {
+        Ok(program) => println!(
+            "{}",
+            program
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use intcode::cfg::Cfg;
+
+fn main() {
+    let args = intcode::cli::Args::from_env();
+    let path = args
+        .rest
+        .first()
+        .expect("At least one command line argument is required.");
+
+    let instructions = args.read_program(path);
+    print!("{}", Cfg::build(&instructions).to_dot());
+}
@@ -0,0 +1,350 @@
+use intcode::disasm::Instruction;
+use intcode::{Intcode, State};
+use std::collections::{BTreeSet, VecDeque};
+use std::io::Write;
+
+static HELP: &str = "Commands:
+  s, step [n]          execute n instructions (default 1)
+  c, continue          run until a breakpoint is hit or the program halts
+  bs, back [n]         undo the last n instructions (default 1)
+  rw, rewind           undo all instructions since the last input was read
+  b, break <addr>      set a breakpoint
+  d, delete <addr>     remove a breakpoint
+  bl, breakpoints      list breakpoints
+  r, regs              show instruction pointer and relative base
+  m, mem <addr> [n]    show n memory cells starting at addr (default 8)
+  p, poke <addr> <v>   set the memory cell at addr to v
+  l, list [addr] [n]   disassemble n instructions (default: 8 at the instruction pointer)
+  i, input <v>...      queue input values
+  is, inputs <text>    queue the characters of text followed by a newline
+  save <file>          write a snapshot of the machine to file
+  load <file>          restore the machine from a snapshot
+  h, help              show this help
+  q, quit              exit the debugger";
+
+// Number of instructions that can be undone.
+const HISTORY_LIMIT: usize = 1_000_000;
+
+fn read_line(prompt: &str) -> Option<String> {
+    print!("{}", prompt);
+    std::io::stdout().flush().unwrap();
+
+    let mut buffer = String::new();
+    match std::io::stdin().read_line(&mut buffer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(buffer.trim().to_string()),
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(args: &[&str], i: usize, default: Option<T>) -> Option<T> {
+    match args.get(i) {
+        Some(a) => a.parse().ok(),
+        None => default,
+    }
+}
+
+struct Debugger {
+    interpreter: Intcode,
+    breakpoints: BTreeSet<usize>,
+    inputs: VecDeque<isize>,
+    steps: usize,
+    stopped: bool,
+}
+
+impl Debugger {
+    fn new(mut interpreter: Intcode) -> Debugger {
+        interpreter.set_history(Some(HISTORY_LIMIT));
+
+        Debugger {
+            interpreter,
+            breakpoints: BTreeSet::new(),
+            inputs: VecDeque::new(),
+            steps: 0,
+            stopped: false,
+        }
+    }
+
+    fn cells(&self, address: usize, n: usize) -> Vec<isize> {
+        // Cells past the end of the address space read as zero.
+        (0..n)
+            .map(|i| {
+                let cell = address
+                    .checked_add(i)
+                    .map(|a| self.interpreter.memory().get(a));
+                cell.and_then(Result::ok).unwrap_or(0)
+            })
+            .collect()
+    }
+
+    fn format_instruction(&self, address: usize) -> (String, usize) {
+        let cells = self.cells(address, 4);
+        let marker = if address == self.interpreter.iptr() {
+            "=>"
+        } else if self.breakpoints.contains(&address) {
+            " *"
+        } else {
+            "  "
+        };
+
+        match Instruction::decode(&cells) {
+            Some(instr) => (
+                format!("{} {:>5}: {}", marker, address, instr),
+                instr.size(),
+            ),
+            None => (format!("{} {:>5}: DATA {}", marker, address, cells[0]), 1),
+        }
+    }
+
+    fn show_current(&self) {
+        println!("{}", self.format_instruction(self.interpreter.iptr()).0);
+    }
+
+    fn next_input(&mut self) -> Option<isize> {
+        if let Some(input) = self.interpreter.recorded_input() {
+            println!("Input: {} (replayed)", input);
+            return Some(input);
+        }
+        if let Some(input) = self.inputs.pop_front() {
+            return Some(input);
+        }
+
+        loop {
+            let line = read_line("Input: ")?;
+            match line.parse() {
+                Ok(input) => return Some(input),
+                Err(_) => println!("Please enter a number."),
+            }
+        }
+    }
+
+    // Executes a single instruction, an input instruction is executed including the read.
+    // Returns false if execution cannot continue.
+    fn single_step(&mut self) -> bool {
+        if self.stopped {
+            println!("The program is not running.");
+            return false;
+        }
+
+        loop {
+            let mut input = 0;
+            if self.interpreter.is_input_requested() {
+                match self.next_input() {
+                    Some(i) => input = i,
+                    None => {
+                        println!("No input available.");
+                        return false;
+                    }
+                }
+            }
+
+            match self.interpreter.try_step_instruction(input) {
+                Ok(None) => break,
+                Ok(Some(State::Input)) => continue,
+                Ok(Some(State::Output(o))) => {
+                    println!("Output: {}", o);
+                    break;
+                }
+                Ok(Some(State::Terminated)) => {
+                    println!("Program terminated after {} steps.", self.steps);
+                    self.stopped = true;
+                    return false;
+                }
+                Err(e) => {
+                    println!("Error: {}", e);
+                    self.stopped = true;
+                    return false;
+                }
+            }
+        }
+
+        self.steps += 1;
+        true
+    }
+
+    fn step(&mut self, n: usize) {
+        for _ in 0..n {
+            if !self.single_step() {
+                return;
+            }
+        }
+        self.show_current();
+    }
+
+    fn back(&mut self, n: usize) {
+        let undone = self.interpreter.step_back(n);
+        self.after_undo(undone);
+    }
+
+    fn rewind(&mut self) {
+        match self.interpreter.rewind_to_input() {
+            Some(undone) => self.after_undo(undone),
+            None => println!("No input in the history."),
+        }
+    }
+
+    fn after_undo(&mut self, undone: usize) {
+        if undone > 0 {
+            self.stopped = false;
+            self.steps = self.steps.saturating_sub(undone);
+        }
+        println!("Undid {} instructions.", undone);
+        self.show_current();
+    }
+
+    fn cont(&mut self) {
+        while self.single_step() {
+            if self.breakpoints.contains(&self.interpreter.iptr()) {
+                println!("Breakpoint at {}", self.interpreter.iptr());
+                self.show_current();
+                return;
+            }
+        }
+    }
+
+    fn show_registers(&self) {
+        println!(
+            "iptr = {}, relative base = {}, steps = {}, memory = {} cells",
+            self.interpreter.iptr(),
+            self.interpreter.relative_base(),
+            self.steps,
+            self.interpreter.memory().high_water_mark()
+        );
+    }
+
+    fn show_memory(&self, address: usize, n: usize) {
+        let n = n.min((usize::MAX - address).saturating_add(1));
+        for (i, chunk) in self.cells(address, n).chunks(8).enumerate() {
+            let values = chunk
+                .iter()
+                .map(|c| format!("{:>8}", c))
+                .collect::<Vec<_>>()
+                .join(" ");
+            println!("{:>5}: {}", address + 8 * i, values);
+        }
+    }
+
+    fn list(&self, mut address: usize, n: usize) {
+        for _ in 0..n {
+            let (line, size) = self.format_instruction(address);
+            println!("{}", line);
+            address = match address.checked_add(size) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    }
+
+    // Returns false if the debugger should exit.
+    fn command(&mut self, line: &str) -> bool {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((c, a)) => (*c, a),
+            None => return true,
+        };
+
+        match command {
+            "s" | "step" => match parse_arg(args, 0, Some(1)) {
+                Some(n) => self.step(n),
+                None => println!("Invalid step count."),
+            },
+            "c" | "continue" => self.cont(),
+            "bs" | "back" => match parse_arg(args, 0, Some(1)) {
+                Some(n) => self.back(n),
+                None => println!("Invalid step count."),
+            },
+            "rw" | "rewind" => self.rewind(),
+            "b" | "break" => match parse_arg(args, 0, None) {
+                Some(a) => {
+                    self.breakpoints.insert(a);
+                }
+                None => println!("Invalid address."),
+            },
+            "d" | "delete" => match parse_arg::<usize>(args, 0, None) {
+                Some(a) => {
+                    if !self.breakpoints.remove(&a) {
+                        println!("No breakpoint at {}", a);
+                    }
+                }
+                None => println!("Invalid address."),
+            },
+            "bl" | "breakpoints" => {
+                for b in &self.breakpoints {
+                    println!("{}", self.format_instruction(*b).0);
+                }
+            }
+            "r" | "regs" => self.show_registers(),
+            "m" | "mem" => match (parse_arg(args, 0, None), parse_arg(args, 1, Some(8))) {
+                (Some(a), Some(n)) => self.show_memory(a, n),
+                _ => println!("Invalid address or count."),
+            },
+            "p" | "poke" => match (parse_arg(args, 0, None), parse_arg(args, 1, None)) {
+                (Some(a), Some(v)) => {
+                    if let Err(e) = self.interpreter.poke(a, v) {
+                        println!("{}", e);
+                    }
+                }
+                _ => println!("Invalid address or value."),
+            },
+            "l" | "list" => {
+                let iptr = self.interpreter.iptr();
+                match (parse_arg(args, 0, Some(iptr)), parse_arg(args, 1, Some(8))) {
+                    (Some(a), Some(n)) => self.list(a, n),
+                    _ => println!("Invalid address or count."),
+                }
+            }
+            "i" | "input" => {
+                let values: Result<Vec<isize>, _> = args.iter().map(|a| a.parse()).collect();
+                match values {
+                    Ok(values) => self.inputs.extend(values),
+                    Err(_) => println!("Inputs need to be numbers."),
+                }
+            }
+            "is" | "inputs" => {
+                let text = line[command.len()..].trim_start();
+                self.inputs.extend(text.bytes().map(|b| b as isize));
+                self.inputs.push_back(10);
+            }
+            "save" => match args.first() {
+                Some(path) => {
+                    if let Err(e) = self.interpreter.save_snapshot(path) {
+                        println!("{}", e);
+                    }
+                }
+                None => println!("Missing file name."),
+            },
+            "load" => match args.first().map(|path| Intcode::load_snapshot(path)) {
+                Some(Ok(mut interpreter)) => {
+                    interpreter.set_history(Some(HISTORY_LIMIT));
+                    self.interpreter = interpreter;
+                    self.stopped = false;
+                    self.show_current();
+                }
+                Some(Err(e)) => println!("{}", e),
+                None => println!("Missing file name."),
+            },
+            "h" | "help" => println!("{}", HELP),
+            "q" | "quit" => return false,
+            _ => println!("Unknown command '{}', type 'help' for a list.", command),
+        }
+
+        true
+    }
+}
+
+fn main() {
+    let args = intcode::cli::Args::from_env();
+    let path = args
+        .rest
+        .first()
+        .expect("At least one command line argument is required.");
+
+    let instructions = args.read_program(path);
+    let mut debugger = Debugger::new(args.machine(instructions));
+
+    debugger.show_current();
+    while let Some(line) = read_line("(icdb) ") {
+        if !debugger.command(&line) {
+            break;
+        }
+    }
+}
@@ -0,0 +1,10 @@
+fn main() {
+    let args = intcode::cli::Args::from_env();
+    let path = args
+        .rest
+        .first()
+        .expect("At least one command line argument is required.");
+
+    let instructions = args.read_program(path);
+    print!("{}", intcode::disasm::listing(&instructions));
+}
@@ -0,0 +1,125 @@
+use intcode::coverage::Coverage;
+use intcode::profile::Profiler;
+use intcode::trace::{JsonTracer, TraceRecord, Tracer};
+use intcode::{AsciiIo, BufIo, Io, State, StdIo};
+use std::fs::File;
+use std::io::BufWriter;
+
+static USAGE: &str = "Usage: intcode-run <program> [--ascii] [--input <v1,v2,...>] \
+                      [--trace <file>] [--profile] [--coverage <file>] [--budget <instructions>] \
+                      [--detect-loops] [--patch <addr>=<value>]... [--record <file> | --replay <file>]";
+
+// Number of addresses and loops shown in the profile.
+const PROFILE_TOP: usize = 20;
+
+struct RunTracer {
+    json: Option<JsonTracer<BufWriter<File>>>,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+}
+
+impl Tracer for RunTracer {
+    fn trace(&mut self, record: &TraceRecord) {
+        if let Some(json) = self.json.as_mut() {
+            json.trace(record);
+        }
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.trace(record);
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.trace(record);
+        }
+    }
+}
+
+fn main() {
+    let cli = intcode::cli::Args::from_env();
+    let mut args = cli.rest.iter().cloned();
+    let mut path = None;
+    let mut ascii = false;
+    let mut input = None;
+    let mut budget = None;
+    let mut detect_loops = false;
+    let mut coverage_path = None;
+    let mut tracer = RunTracer {
+        json: None,
+        profiler: None,
+        coverage: None,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ascii" => ascii = true,
+            "--input" => {
+                let values = args.next().expect(USAGE);
+                input = Some(intcode::parse_intcode(&values).expect(USAGE));
+            }
+            "--trace" => {
+                let file =
+                    File::create(args.next().expect(USAGE)).expect("Could not create trace file");
+                tracer.json = Some(JsonTracer::new(BufWriter::new(file)));
+            }
+            "--profile" => tracer.profiler = Some(Profiler::new()),
+            "--coverage" => {
+                coverage_path = Some(args.next().expect(USAGE));
+                tracer.coverage = Some(Coverage::new());
+            }
+            "--budget" => budget = Some(args.next().expect(USAGE).parse().expect(USAGE)),
+            "--detect-loops" => detect_loops = true,
+            _ if path.is_none() => path = Some(arg),
+            _ => panic!("{}", USAGE),
+        }
+    }
+
+    let instructions = cli.read_program(&path.expect(USAGE));
+    let mut interpreter = cli.machine(instructions.clone());
+    interpreter.set_instruction_budget(budget);
+    interpreter.set_loop_detection(detect_loops);
+
+    let input = input.unwrap_or_default();
+    let mut buf_io = BufIo::new(&input);
+    let mut ascii_io = AsciiIo::new();
+    let mut std_io = StdIo::new();
+    let io: &mut dyn Io = if ascii {
+        &mut ascii_io
+    } else if !input.is_empty() {
+        &mut buf_io
+    } else {
+        &mut std_io
+    };
+
+    let traced = tracer.json.is_some() || tracer.profiler.is_some() || tracer.coverage.is_some();
+    let result = if traced {
+        interpreter.run_traced(io, &mut tracer)
+    } else {
+        interpreter.run(io)
+    };
+
+    for o in buf_io.output() {
+        println!("{}", o);
+    }
+
+    if let Some(json) = tracer.json {
+        json.finish().expect("Error while writing trace.");
+    }
+    if let Some(profiler) = tracer.profiler {
+        eprint!("{}", profiler.report(PROFILE_TOP));
+    }
+    if let (Some(coverage), Some(path)) = (tracer.coverage, coverage_path) {
+        std::fs::write(&path, coverage.annotate(&instructions))
+            .expect("Could not write coverage file");
+        eprint!("{}", coverage.summary(&instructions));
+    }
+
+    match result {
+        Ok(State::Input) => {
+            eprintln!("Input starved at {}", interpreter.iptr());
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        Ok(_) => (),
+    }
+}
@@ -0,0 +1,20 @@
+static USAGE: &str = "Usage: intcode-transpile <program> [--main] [--patch <addr>=<value>]...";
+
+fn main() {
+    let mut path = None;
+    let mut with_main = false;
+    let cli = intcode::cli::Args::from_env();
+    for arg in cli.rest.iter().cloned() {
+        match arg.as_str() {
+            "--main" => with_main = true,
+            _ if path.is_none() => path = Some(arg),
+            _ => panic!("{}", USAGE),
+        }
+    }
+
+    let instructions = cli.read_program(&path.expect(USAGE));
+    print!(
+        "{}",
+        intcode::transpile::transpile(&instructions, with_main)
+    );
+}
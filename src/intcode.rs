@@ -1,4 +1,38 @@
+use std::collections::BTreeMap;
+use std::fmt;
 use std::io::{Read, Write};
+use std::sync::Arc;
+
+pub mod asm;
+mod cell;
+pub mod cfg;
+pub mod cli;
+pub mod coverage;
+mod decode;
+pub mod decompile;
+pub mod disasm;
+pub mod extension;
+pub mod framing;
+mod history;
+mod memory;
+pub mod profile;
+pub mod recording;
+pub mod scheduler;
+pub mod snapshot;
+pub mod symbolic;
+pub mod threaded;
+pub mod trace;
+pub mod transpile;
+mod watchdog;
+
+pub use cell::Cell;
+use decode::{DecodeCache, Decoded, Operand};
+use extension::{Param, Registered};
+use history::{History, Undo};
+pub use memory::{Memory, DEFAULT_MEMORY_LIMIT};
+use recording::SharedTap;
+pub use trace::Tracer;
+use watchdog::LoopDetector;
 
 #[test]
 fn test_examples() {
@@ -33,6 +67,7 @@ fn test_decode() {
 }
 
 #[test]
+#[allow(clippy::needless_range_loop)]
 fn test_relative() {
     let instr = vec![
         109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
@@ -47,6 +82,7 @@ fn test_relative() {
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_large_num() {
     let instr = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0];
     let mut io = BufIo::new(&[]);
@@ -62,20 +98,331 @@ fn test_large_num_2() {
     assert_eq!(1125899906842624, io.get(0));
 }
 
-pub trait Io {
-    fn input(&mut self) -> isize;
-    fn output(&mut self, o: isize);
+#[test]
+fn test_unknown_opcode() {
+    let mut interpreter = Intcode::new(vec![1101, 1, 2, 5, 42, 0]);
+    let err = interpreter.try_step(0).unwrap_err();
+
+    assert_eq!(ErrorKind::InvalidOpcode, err.kind);
+    assert_eq!(4, err.iptr);
+    assert_eq!(42, err.opcode);
+    assert_eq!([0, 0, 0], err.modes);
+}
+
+#[test]
+fn test_immediate_store() {
+    let mut interpreter = Intcode::new(vec![109, 7, 11101, 1, 2, 5, 99]);
+    let err = interpreter.try_step(0).unwrap_err();
+
+    assert_eq!(ErrorKind::ImmediateStore, err.kind);
+    assert_eq!(2, err.iptr);
+    assert_eq!(11101, err.opcode);
+    assert_eq!([1, 1, 1], err.modes);
+    assert_eq!(7, err.base);
+}
+
+#[test]
+fn test_negative_address() {
+    let mut io = BufIo::new(&[]);
+    let err = try_evaluate_io(vec![4, -3, 99], &mut io).unwrap_err();
+
+    assert_eq!(ErrorKind::NegativeAddress(-3), err.kind);
+    assert_eq!(0, err.iptr);
+}
+
+#[test]
+fn test_memory_growth() {
+    let mut interpreter = Intcode::new(vec![1101, 2, 3, 20_000, 4, 20_000, 99]);
+    assert_eq!(Ok(State::Output(5)), interpreter.try_step(0));
+    assert_eq!(20_001, interpreter.memory().high_water_mark());
+
+    let mut interpreter = Intcode::with_memory_limit(vec![1101, 2, 3, 20_000, 99], 100);
+    assert_eq!(
+        ErrorKind::MemoryLimit(20_000),
+        interpreter.try_step(0).unwrap_err().kind
+    );
+}
+
+#[test]
+fn test_step_instruction() {
+    let mut interpreter = Intcode::new(vec![109, 5, 3, 9, 204, 4, 99]);
+
+    assert_eq!(Ok(None), interpreter.try_step_instruction(0));
+    assert_eq!((2, 5), (interpreter.iptr(), interpreter.relative_base()));
+    assert_eq!(Ok(Some(State::Input)), interpreter.try_step_instruction(0));
+    assert!(interpreter.is_input_requested());
+    assert_eq!(Ok(None), interpreter.try_step_instruction(42));
+    assert_eq!(4, interpreter.iptr());
+    assert_eq!(
+        Ok(Some(State::Output(42))),
+        interpreter.try_step_instruction(0)
+    );
+    assert_eq!(
+        Ok(Some(State::Terminated)),
+        interpreter.try_step_instruction(0)
+    );
+    assert!(interpreter.is_terminated());
+}
+
+#[test]
+fn test_after_termination() {
+    let mut interpreter = Intcode::new(vec![1101, 1, 2, 5, 99, 0]);
+    interpreter.set_instruction_budget(Some(2));
+    interpreter.set_history(Some(10));
+    let mut trace = Vec::new();
+
+    for _ in 0..3 {
+        assert_eq!(
+            Ok(State::Terminated),
+            interpreter.try_step_traced(0, &mut trace)
+        );
+    }
+    assert_eq!(
+        Ok(Some(State::Terminated)),
+        interpreter.try_step_instruction(0)
+    );
+    assert_eq!(2, interpreter.instructions_retired());
+    assert_eq!(Some(0), interpreter.instruction_budget());
+    assert_eq!(2, interpreter.history_len());
+    assert_eq!(2, trace.len());
+
+    // Stepping back resumes the machine before the halt instruction.
+    assert_eq!(1, interpreter.step_back(1));
+    interpreter.set_instruction_budget(None);
+    assert_eq!(Ok(State::Terminated), interpreter.try_step(0));
+    assert_eq!(2, interpreter.instructions_retired());
+}
+
+#[test]
+fn test_fork() {
+    // Adds two inputs and outputs the sum.
+    let instr = vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0];
+    let mut interpreter = Intcode::new(instr);
+
+    assert_eq!(State::Input, interpreter.step(0));
+    assert_eq!(State::Input, interpreter.step(10));
+
+    let mut outputs = Vec::new();
+    for i in 0..3 {
+        let mut fork = interpreter.fork();
+        assert_eq!(State::Output(10 + i), fork.step(i));
+        outputs.push(fork);
+    }
+
+    assert_eq!(State::Output(15), interpreter.step(5));
+    assert_eq!(State::Terminated, interpreter.step(0));
+    assert_eq!(Ok(12), outputs[2].memory().get(13));
+    assert_eq!(Ok(15), interpreter.memory().get(13));
+}
+
+#[test]
+fn test_overflow() {
+    let instr = vec![1102, 1 << 40, 1 << 40, 9, 1101, isize::MAX, 1, 9, 99, 0];
+    let mut interpreter = Intcode::new(instr.clone());
+    interpreter.set_arithmetic(Arithmetic::Checked);
+    let err = interpreter.try_step(0).unwrap_err();
+    assert_eq!(ErrorKind::Overflow, err.kind);
+    assert_eq!(0, err.iptr);
+
+    let mut interpreter = Intcode::new(instr.clone());
+    assert_eq!(Ok(State::Terminated), interpreter.try_step(0));
+    assert_eq!(Ok(isize::MIN), interpreter.memory().get(9));
+
+    let mut interpreter = Intcode::<i128>::widen(&instr);
+    interpreter.set_arithmetic(Arithmetic::Checked);
+    assert_eq!(Ok(State::Terminated), interpreter.try_step(0));
+    assert_eq!(Ok(isize::MAX as i128 + 1), interpreter.memory().get(9));
+}
+
+#[test]
+fn test_wide() {
+    // Squares the input twice and outputs the result.
+    let instr = vec![3, 13, 2, 13, 13, 13, 2, 13, 13, 13, 4, 13, 99, 0];
+
+    let mut interpreter = Intcode::<i128>::widen(&instr);
+    interpreter.set_arithmetic(Arithmetic::Checked);
+    assert_eq!(State::Input, interpreter.step(0));
+    assert_eq!(State::Output(1 << 100), interpreter.step(1 << 25));
+}
+
+#[test]
+fn test_decode_cache() {
+    // Patches the first operand of its own ADD instruction on every iteration.
+    let program = asm::assemble(
+        "
+        add:    ADD #0, #1, [acc]
+                OUT [acc]
+                ADD [acc], #0, [add+1]
+                LT [acc], #5, [cond]
+                JNZ [cond], #add
+                HLT
+        acc:    .data 0
+        cond:   .data 0
+        ",
+    )
+    .unwrap();
+
+    for &cached in &[true, false] {
+        let mut interpreter = Intcode::new(program.clone());
+        interpreter.set_decode_cache(cached);
+        let mut outputs = Vec::new();
+        while let State::Output(o) = interpreter.step(0) {
+            outputs.push(o);
+            assert!(outputs.len() <= 5, "Stale instruction was executed");
+        }
+        assert_eq!(vec![1, 2, 3, 4, 5], outputs);
+    }
+}
+
+#[test]
+fn test_budget() {
+    let mut interpreter = Intcode::new(vec![1101, 1, 2, 7, 4, 7, 99, 0]);
+    interpreter.set_instruction_budget(Some(1));
+    let err = interpreter.try_step(0).unwrap_err();
+    assert_eq!(ErrorKind::BudgetExhausted, err.kind);
+    assert_eq!(4, err.iptr);
+    assert_eq!(Some(0), interpreter.instruction_budget());
+
+    interpreter.set_instruction_budget(Some(10));
+    assert_eq!(Ok(State::Output(3)), interpreter.try_step(0));
+    assert_eq!(Some(9), interpreter.instruction_budget());
+}
+
+#[test]
+fn test_loop_detection() {
+    // Outputs a countdown, then toggles a flag forever.
+    let program = asm::assemble(
+        "
+        count:  OUT [n]
+                ADD [n], #-1, [n]
+                JNZ [n], #count
+        toggle: EQ [flag], #0, [flag]
+                JNZ #1, #toggle
+        n:      .data 3
+        flag:   .data 0
+        ",
+    )
+    .unwrap();
+
+    let mut interpreter = Intcode::new(program);
+    interpreter.set_loop_detection(true);
+    for i in (1..=3).rev() {
+        assert_eq!(Ok(State::Output(i)), interpreter.try_step(0));
+    }
+    let err = interpreter.try_step(0).unwrap_err();
+    assert_eq!(ErrorKind::Looping, err.kind);
+    assert!(interpreter.instructions_retired() < 30);
+}
+
+#[test]
+fn test_step_back() {
+    // Adds two inputs and outputs the sum.
+    let instr = vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0];
+    let mut interpreter = Intcode::new(instr);
+    interpreter.set_history(Some(100));
+
+    assert_eq!(State::Input, interpreter.step(0));
+    assert_eq!(State::Input, interpreter.step(10));
+    assert_eq!(State::Output(15), interpreter.step(5));
+    assert_eq!(4, interpreter.history_len());
+
+    assert_eq!(2, interpreter.step_back(2));
+    assert_eq!(4, interpreter.iptr());
+    assert_eq!(Ok(0), interpreter.memory().get(13));
+
+    assert_eq!(Some(1), interpreter.rewind_to_input());
+    assert_eq!(Ok(0), interpreter.memory().get(12));
+    assert_eq!(State::Input, interpreter.step(0));
+    assert_eq!(State::Output(17), interpreter.step(7));
+    assert_eq!(4, interpreter.instructions_retired());
+
+    assert_eq!(4, interpreter.step_back(10));
+    assert_eq!(None, interpreter.rewind_to_input());
+    assert_eq!(
+        (0, Ok(0)),
+        (interpreter.iptr(), interpreter.memory().get(11))
+    );
+}
+
+#[test]
+fn test_poke() {
+    // Outputs the immediate at address 1 forever.
+    let mut interpreter = Intcode::with_memory_limit(vec![104, 7, 1105, 1, 0], 100);
+    assert_eq!(State::Output(7), interpreter.step(0));
+
+    assert_eq!(Ok(7), interpreter.poke(1, 8));
+    assert_eq!(Ok(8), interpreter.peek(1));
+    assert_eq!(State::Output(8), interpreter.step(0));
+
+    assert_eq!(Ok(vec![1105, 1, 0, 0]), interpreter.peek_range(2, 4));
+    assert_eq!(Err(ErrorKind::MemoryLimit(100)), interpreter.peek(100));
+    assert!(interpreter.poke(100, 1).is_err());
+    assert_eq!(
+        Err(ErrorKind::Overflow),
+        interpreter.peek_range(usize::MAX, 2)
+    );
+}
+
+#[test]
+fn test_input_starved() {
+    // Adds two inputs and outputs the sum.
+    let instr = vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0];
+    let mut interpreter = Intcode::new(instr.clone());
+    let mut io = BufIo::new(&[3]);
+    assert_eq!(Ok(State::Input), interpreter.run(&mut io));
+    assert_eq!(Ok(State::Input), interpreter.run(&mut io));
+    assert!(io.is_empty());
+
+    io.set_input(&[4]);
+    assert_eq!(Ok(State::Terminated), interpreter.run(&mut io));
+    assert_eq!(&vec![7], io.output());
+
+    let err = try_evaluate_io(instr, &mut BufIo::new(&[1])).unwrap_err();
+    assert_eq!((ErrorKind::InputStarved, 2), (err.kind, err.iptr));
+}
+
+#[test]
+fn test_parse_error() {
+    match parse_intcode("1,2,x,4") {
+        Err(LoadError::Parse(2, _)) => (),
+        _ => panic!("Expected parse error for the third cell"),
+    }
+    assert_eq!(vec![1, -2, 99], parse_intcode("1,-2,99\n").unwrap());
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum InputError {
+    // No input is available at the moment. A machine run with the Io stops waiting for input and
+    // can be resumed once there is more.
+    End,
+    // The input could not be read or is not a number.
+    Invalid,
 }
 
-struct StdIo;
+pub trait Io<C = isize> {
+    fn input(&mut self) -> Result<C, InputError>;
+    fn output(&mut self, o: C);
+}
+
+#[derive(Default)]
+pub struct StdIo;
+
+impl StdIo {
+    pub fn new() -> StdIo {
+        StdIo {}
+    }
+}
 
 impl Io for StdIo {
-    fn input(&mut self) -> isize {
+    fn input(&mut self) -> Result<isize, InputError> {
         print!("Input: ");
         std::io::stdout().flush().unwrap();
         let mut buffer = String::new();
-        std::io::stdin().read_line(&mut buffer).unwrap();
-        str::parse(&buffer.trim()).unwrap()
+        match std::io::stdin().read_line(&mut buffer) {
+            Ok(0) => Err(InputError::End),
+            Ok(_) => str::parse(buffer.trim()).map_err(|_| InputError::Invalid),
+            Err(_) => Err(InputError::Invalid),
+        }
     }
 
     fn output(&mut self, o: isize) {
@@ -83,6 +430,7 @@ impl Io for StdIo {
     }
 }
 
+#[derive(Default)]
 pub struct AsciiIo;
 
 impl AsciiIo {
@@ -92,10 +440,13 @@ impl AsciiIo {
 }
 
 impl Io for AsciiIo {
-    fn input(&mut self) -> isize {
+    fn input(&mut self) -> Result<isize, InputError> {
         let mut buffer = [0; 1];
-        std::io::stdin().read(&mut buffer).unwrap();
-        buffer[0] as isize
+        match std::io::stdin().read(&mut buffer) {
+            Ok(0) => Err(InputError::End),
+            Ok(_) => Ok(buffer[0] as isize),
+            Err(_) => Err(InputError::Invalid),
+        }
     }
 
     fn output(&mut self, o: isize) {
@@ -118,6 +469,12 @@ impl<'a> BufIo<'a> {
         }
     }
 
+    // Continues reading input from the given slice, whatever was left unread is dropped.
+    pub fn set_input(&mut self, input: &'a [isize]) {
+        self.buf_in = input;
+        self.cursor_in = 0;
+    }
+
     pub fn output(&self) -> &Vec<isize> {
         &self.buf_out
     }
@@ -129,13 +486,17 @@ impl<'a> BufIo<'a> {
     pub fn len(&self) -> usize {
         self.buf_out.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf_out.is_empty()
+    }
 }
 
 impl<'a> Io for BufIo<'a> {
-    fn input(&mut self) -> isize {
-        let result = self.buf_in[self.cursor_in];
+    fn input(&mut self) -> Result<isize, InputError> {
+        let result = *self.buf_in.get(self.cursor_in).ok_or(InputError::End)?;
         self.cursor_in += 1;
-        result
+        Ok(result)
     }
 
     fn output(&mut self, o: isize) {
@@ -143,18 +504,12 @@ impl<'a> Io for BufIo<'a> {
     }
 }
 
-pub fn evaluate_io(instructions: Vec<isize>, io: &mut dyn Io) -> isize {
-    let mut interpreter = Intcode::new(instructions);
+pub fn try_evaluate_io(instructions: Vec<isize>, io: &mut dyn Io) -> Result<isize, IntcodeError> {
+    Intcode::new(instructions).evaluate(io)
+}
 
-    let mut input = 0;
-    loop {
-        match interpreter.step(input) {
-            State::Input => input = io.input(),
-            State::Output(o) => io.output(o),
-            State::Terminated => break,
-        }
-    }
-    interpreter.first_cell()
+pub fn evaluate_io(instructions: Vec<isize>, io: &mut dyn Io) -> isize {
+    try_evaluate_io(instructions, io).unwrap_or_else(|e| panic!("{}", e))
 }
 
 pub fn evaluate(instructions: Vec<isize>) -> isize {
@@ -178,172 +533,804 @@ fn decode_opcode(mut op: isize) -> [u8; 4] {
     result
 }
 
-pub fn read_intcode_file(path: &str) -> Vec<isize> {
-    let mut code = String::new();
-    std::fs::File::open(path)
-        .expect("Could not open input file")
-        .read_to_string(&mut code)
-        .expect("Error while reading from file.");
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    Parse(usize, std::num::ParseIntError),
+}
 
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "Error while reading Intcode file: {}", e),
+            LoadError::Parse(i, e) => write!(f, "Invalid Intcode cell at index {}: {}", i, e),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+pub fn parse_intcode(code: &str) -> Result<Vec<isize>, LoadError> {
     code.trim()
         .split(',')
-        .map(|x| str::parse(x).unwrap())
+        .enumerate()
+        .map(|(i, x)| str::parse(x.trim()).map_err(|e| LoadError::Parse(i, e)))
         .collect()
 }
 
-#[derive(PartialEq)]
-pub enum State {
+pub fn try_read_intcode_file(path: &str) -> Result<Vec<isize>, LoadError> {
+    let mut code = String::new();
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut code))
+        .map_err(LoadError::Io)?;
+
+    parse_intcode(&code)
+}
+
+pub fn read_intcode_file(path: &str) -> Vec<isize> {
+    try_read_intcode_file(path).unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[derive(PartialEq, Debug)]
+pub enum State<C = isize> {
     Terminated,
-    Output(isize),
+    Output(C),
     Input,
 }
 
-pub struct Intcode {
-    instructions: Vec<isize>,
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ErrorKind {
+    NegativeAddress(isize),
+    ImmediateStore,
+    InvalidMode(u8),
+    InvalidOpcode,
+    InvalidJump(isize),
+    MemoryLimit(usize),
+    Overflow,
+    BudgetExhausted,
+    Looping,
+    InvalidInput,
+    InputStarved,
+    Diverged,
+    RecordingFailed,
+}
+
+// Describes a fault of the interpreter. The instruction pointer, opcode and modes refer to the
+// instruction that could not be executed. If the instruction itself lies beyond the memory limit,
+// the opcode and modes are zero.
+#[derive(PartialEq, Debug, Clone)]
+pub struct IntcodeError {
+    pub kind: ErrorKind,
+    pub iptr: usize,
+    pub opcode: isize,
+    pub modes: [u8; 3],
+    pub base: isize,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::NegativeAddress(a) => write!(f, "Encountered negative position {}", a),
+            ErrorKind::ImmediateStore => write!(f, "Immediate mode not supported for store"),
+            ErrorKind::InvalidMode(m) => write!(f, "Unhandled parameter mode {}", m),
+            ErrorKind::InvalidOpcode => write!(f, "Unhandled opcode"),
+            ErrorKind::InvalidJump(a) => write!(f, "Invalid instruction pointer {}", a),
+            ErrorKind::MemoryLimit(a) => write!(f, "Address {} exceeds memory limit", a),
+            ErrorKind::Overflow => write!(f, "Arithmetic overflow"),
+            ErrorKind::BudgetExhausted => write!(f, "Instruction budget exhausted"),
+            ErrorKind::Looping => write!(f, "Machine revisited a state without performing I/O"),
+            ErrorKind::InvalidInput => write!(f, "Could not read input"),
+            ErrorKind::InputStarved => write!(f, "Program requested more input than available"),
+            ErrorKind::Diverged => write!(f, "Program diverged from the recording"),
+            ErrorKind::RecordingFailed => write!(f, "Could not write the recording"),
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at {} (opcode {}, modes {:?}, relative base {})",
+            self.kind, self.iptr, self.opcode, self.modes, self.base
+        )
+    }
+}
+
+impl std::error::Error for IntcodeError {}
+
+// How additions and multiplications treat results that do not fit into a cell. Checked
+// arithmetic fails with ErrorKind::Overflow at the offending instruction.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Arithmetic {
+    Wrapping,
+    Checked,
+}
+
+// Address computations (relative base, relative operands) are always checked, independent of the
+// arithmetic mode.
+#[derive(Clone)]
+pub struct Intcode<C: Cell = isize> {
+    memory: Memory<C>,
     base: isize,
     iptr: usize,
     input_requested: bool,
+    arithmetic: Arithmetic,
+    cache: DecodeCache<C>,
+    budget: Option<u64>,
+    loops: Option<LoopDetector<C>>,
+    history: Option<History<C>>,
+    retired: u64,
+    // Set once the halt instruction was executed, later steps do not execute it again.
+    halted: bool,
+    // Address, previous and new value of the cell written by the last instruction.
+    last_write: Option<(usize, C, C)>,
+    // The tap and the number of the machine it knows it by.
+    tap: Option<(SharedTap<C>, usize)>,
+    extensions: BTreeMap<u8, Arc<Registered<C>>>,
 }
 
 impl Intcode {
-    pub fn new(mut instructions: Vec<isize>) -> Intcode {
-        instructions.extend([0; 10000].iter());
+    pub fn new(instructions: Vec<isize>) -> Intcode {
+        Intcode::with_memory_limit(instructions, DEFAULT_MEMORY_LIMIT)
+    }
+
+    pub fn with_memory_limit(instructions: Vec<isize>, limit: usize) -> Intcode {
+        Intcode::from_cells(instructions, limit)
+    }
+}
+
+impl<C: Cell> Intcode<C> {
+    pub fn from_cells(cells: Vec<C>, limit: usize) -> Intcode<C> {
+        Intcode::from_memory(Memory::from_cells(cells, limit), Arithmetic::Wrapping)
+    }
+
+    // A machine at the start of the program in memory.
+    pub fn from_memory(memory: Memory<C>, arithmetic: Arithmetic) -> Intcode<C> {
+        let cache = DecodeCache::new(memory.high_water_mark());
         Intcode {
-            instructions,
+            memory,
             base: 0,
             iptr: 0,
             input_requested: false,
+            arithmetic,
+            cache,
+            budget: None,
+            loops: None,
+            history: None,
+            retired: 0,
+            halted: false,
+            last_write: None,
+            tap: None,
+            extensions: BTreeMap::new(),
         }
     }
 
-    pub fn is_terminated(&self) -> bool {
-        self.instructions[self.iptr] == 99
+    // Continues execution from the given memory contents, instruction pointer and relative base.
+    pub fn from_state(cells: Vec<C>, iptr: usize, base: isize) -> Intcode<C> {
+        let mut interpreter = Intcode::from_cells(cells, DEFAULT_MEMORY_LIMIT);
+        interpreter.iptr = iptr;
+        interpreter.base = base;
+        interpreter
     }
 
-    pub fn first_cell(&self) -> isize {
-        self.instructions[0]
+    // Loads a program into a machine with a different cell type, e.g.
+    // Intcode::<i128>::widen(&program) for programs whose values exceed 64 bits.
+    pub fn widen(program: &[isize]) -> Intcode<C> {
+        let cells = program.iter().map(|&v| C::from_isize(v)).collect();
+        Intcode::from_cells(cells, DEFAULT_MEMORY_LIMIT)
     }
 
-    fn decode_opcode(&self) -> [u8; 4] {
-        decode_opcode(self.instructions[self.iptr])
+    pub fn arithmetic(&self) -> Arithmetic {
+        self.arithmetic
     }
 
-    fn load_argument(&self, pos: usize, mode: &[u8; 4]) -> isize {
-        let value = self.instructions[self.iptr + pos];
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.arithmetic = arithmetic;
+    }
 
-        match mode[pos] {
-            0 => {
-                if value < 0 {
-                    panic!("Encountered negative position!");
-                }
-                self.instructions[value as usize]
+    pub fn is_decode_cache_enabled(&self) -> bool {
+        self.cache.is_enabled()
+    }
+
+    // Decoded instructions are cached per address unless disabled here. The cache gives the same
+    // results as decoding every instruction, writes into code invalidate the affected entries.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.cache.set_enabled(enabled);
+    }
+
+    pub fn instruction_budget(&self) -> Option<u64> {
+        self.budget
+    }
+
+    // Limits the number of instructions that are executed from now on. Once the budget is used
+    // up, execution fails with ErrorKind::BudgetExhausted before the next instruction; the
+    // machine can be resumed after setting a new budget.
+    pub fn set_instruction_budget(&mut self, budget: Option<u64>) {
+        self.budget = budget;
+    }
+
+    pub fn is_loop_detection_enabled(&self) -> bool {
+        self.loops.is_some()
+    }
+
+    // With loop detection, execution fails with ErrorKind::Looping as soon as the machine
+    // returns to an identical state (memory, instruction pointer and relative base) without
+    // performing I/O in between, as it would never leave that loop. Detection can take up to
+    // twice the number of instructions of loop and lead-in.
+    pub fn set_loop_detection(&mut self, enabled: bool) {
+        self.loops = if enabled {
+            Some(LoopDetector::new(&self.memory))
+        } else {
+            None
+        };
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, |h| h.len())
+    }
+
+    // Records an undo log of the last limit instructions, so the machine can be stepped
+    // backwards. None stops recording and drops the log.
+    pub fn set_history(&mut self, limit: Option<usize>) {
+        self.history = limit.map(History::new);
+    }
+
+    // Undoes up to n instructions and returns how many were undone. Output cannot be taken
+    // back; an input instruction that is undone requests its input again.
+    pub fn step_back(&mut self, n: usize) -> usize {
+        let mut undone = 0;
+        while undone < n {
+            match self.history.as_mut().and_then(|h| h.pop()) {
+                Some(undo) => self.undo(undo),
+                None => break,
             }
-            1 => value,
-            2 => self.instructions[(self.base + value) as usize],
-            _ => panic!("Unhandled parameter mode!"),
+            undone += 1;
         }
+        if let Some((tap, machine)) = self.tap.as_ref().filter(|_| undone > 0) {
+            tap.lock().unwrap().step_back(*machine, self.retired);
+        }
+        undone
     }
 
-    fn store(&mut self, pos: usize, value: isize, opcode: &[u8; 4]) {
-        let address = self.instructions[self.iptr + pos];
+    // Undoes all instructions up to and including the last one that read input, so the machine
+    // requests that input again. Returns the number of undone instructions, or None if the
+    // history holds no input.
+    pub fn rewind_to_input(&mut self) -> Option<usize> {
+        let n = self.history.as_ref()?.since_input()?;
+        Some(self.step_back(n))
+    }
 
-        match opcode[pos] {
-            0 => {
-                if address < 0 {
-                    panic!("Encountered negative position!");
-                }
-                self.instructions[address as usize] = value;
+    fn undo(&mut self, undo: Undo<C>) {
+        if let Some((address, value)) = undo.write {
+            // The cell was written before, so it lies within the memory limit.
+            self.write_cell(address, value).unwrap();
+        }
+        if let Some(loops) = self.loops.as_mut() {
+            loops.reset();
+        }
+
+        self.iptr = undo.iptr;
+        self.base = undo.base;
+        self.input_requested = false;
+        self.halted = false;
+        self.retired -= 1;
+        self.last_write = None;
+    }
+
+    // Records or replays the values the program reads and writes, see recording.
+    pub fn set_tap(&mut self, tap: SharedTap<C>, machine: usize) {
+        self.tap = Some((tap, machine));
+    }
+
+    // Sets the tap, as the next machine it has not seen yet.
+    pub fn attach_tap(&mut self, tap: SharedTap<C>) {
+        let machine = tap.lock().unwrap().attach();
+        self.tap = Some((tap, machine));
+    }
+
+    // The input the machine reads next while replaying a recording, if it requested input.
+    pub fn recorded_input(&self) -> Option<C> {
+        match &self.tap {
+            Some((tap, machine)) if self.input_requested => {
+                tap.lock().unwrap().recorded_input(*machine, self.retired)
             }
-            1 => panic!("Absolute mode not supported for store operation"),
-            2 => {
-                self.instructions[(self.base + address) as usize] = value;
+            _ => None,
+        }
+    }
+
+    pub fn is_terminated(&self) -> bool {
+        self.memory.get(self.iptr) == Ok(C::from_isize(99))
+    }
+
+    pub fn first_cell(&self) -> C {
+        self.memory.get(0).unwrap_or_default()
+    }
+
+    pub fn peek(&self, address: usize) -> Result<C, ErrorKind> {
+        self.memory.get(address)
+    }
+
+    pub fn peek_range(&self, address: usize, len: usize) -> Result<Vec<C>, ErrorKind> {
+        let end = address.checked_add(len).ok_or(ErrorKind::Overflow)?;
+        (address..end).map(|a| self.memory.get(a)).collect()
+    }
+
+    // Overwrites a memory cell and returns its previous value. Decoded instructions covering the
+    // cell are discarded. The write is not part of the history, stepping back keeps the value.
+    pub fn poke(&mut self, address: usize, value: C) -> Result<C, ErrorKind> {
+        self.write_cell(address, value)
+    }
+
+    // Duplicates the machine including its pending input request. Both machines share memory
+    // pages until either of them writes to it. The fork is attached to the tap as a new machine.
+    pub fn fork(&mut self) -> Intcode<C> {
+        self.memory.share();
+        let mut fork = self.clone();
+        if let Some((tap, _)) = &self.tap {
+            fork.attach_tap(tap.clone());
+        }
+        fork
+    }
+
+    pub fn memory(&self) -> &Memory<C> {
+        &self.memory
+    }
+
+    pub fn iptr(&self) -> usize {
+        self.iptr
+    }
+
+    pub fn relative_base(&self) -> isize {
+        self.base
+    }
+
+    pub fn is_input_requested(&self) -> bool {
+        self.input_requested
+    }
+
+    pub fn instructions_retired(&self) -> u64 {
+        self.retired
+    }
+
+    fn fetch(&mut self) -> Result<Decoded<C>, ErrorKind> {
+        if let Some(decoded) = self.cache.get(self.iptr) {
+            return Ok(*decoded);
+        }
+
+        let opcode = self.memory.get(self.iptr)?;
+        let code = decode_opcode(opcode.to_isize().ok_or(ErrorKind::InvalidOpcode)?);
+        let arity = match disasm::Op::from_code(code[0] as isize) {
+            Some(op) => op.arity(),
+            None => self.extensions.get(&code[0]).map_or(0, |e| e.params.len()),
+        };
+
+        let mut operands = [Operand::Immediate(C::default()); 3];
+        for (i, operand) in operands.iter_mut().enumerate().take(arity) {
+            *operand = Operand::new(self.read((self.iptr + i + 1) as isize), code[i + 1]);
+        }
+
+        let decoded = Decoded {
+            code: code[0],
+            operands,
+            size: arity + 1,
+        };
+        self.cache.insert(self.iptr, decoded);
+        Ok(decoded)
+    }
+
+    fn fault(&self, kind: ErrorKind) -> IntcodeError {
+        let opcode = self.memory.get(self.iptr).ok();
+        let opcode = opcode.and_then(Cell::to_isize).unwrap_or(0);
+        let decoded = decode_opcode(opcode);
+        let modes = [decoded[1], decoded[2], decoded[3]];
+
+        IntcodeError {
+            kind,
+            iptr: self.iptr,
+            opcode,
+            modes,
+            base: self.base,
+        }
+    }
+
+    fn read(&self, address: isize) -> Result<C, ErrorKind> {
+        if address < 0 {
+            return Err(ErrorKind::NegativeAddress(address));
+        }
+        self.memory.get(address as usize)
+    }
+
+    // Relative base plus the given cell, for the relative base adjustment.
+    fn relative(&self, offset: C) -> Result<isize, ErrorKind> {
+        self.relative_address(offset.to_isize().ok_or(ErrorKind::Overflow)?)
+    }
+
+    fn relative_address(&self, offset: isize) -> Result<isize, ErrorKind> {
+        self.base.checked_add(offset).ok_or(ErrorKind::Overflow)
+    }
+
+    // The address of a position or relative operand.
+    fn address(&self, operand: Operand<C>) -> Result<usize, ErrorKind> {
+        match operand {
+            Operand::Position(address) => Ok(address),
+            Operand::Immediate(_) => Err(ErrorKind::ImmediateStore),
+            Operand::Relative(offset) => match self.relative_address(offset)? {
+                address if address < 0 => Err(ErrorKind::NegativeAddress(address)),
+                address => Ok(address as usize),
+            },
+            Operand::Invalid(e) => Err(e),
+        }
+    }
+
+    fn load_argument(&self, pos: usize, opcode: &Decoded<C>) -> Result<C, ErrorKind> {
+        match opcode.operands[pos - 1] {
+            Operand::Position(address) => self.memory.get(address),
+            Operand::Immediate(value) => Ok(value),
+            operand => self.memory.get(self.address(operand)?),
+        }
+    }
+
+    fn store(&mut self, pos: usize, value: C, opcode: &Decoded<C>) -> Result<(), ErrorKind> {
+        let address = self.address(opcode.operands[pos - 1])?;
+        let old = self.write_cell(address, value)?;
+        self.last_write = Some((address, old, value));
+        Ok(())
+    }
+
+    // Writes a cell and keeps the decode cache and loop detection up to date. Returns the
+    // previous value.
+    fn write_cell(&mut self, address: usize, value: C) -> Result<C, ErrorKind> {
+        let old = self.memory.set(address, value)?;
+        self.cache.invalidate(address);
+        if address == self.iptr {
+            self.halted = false;
+        }
+        if let Some(loops) = self.loops.as_mut() {
+            loops.write(address, old, value);
+        }
+        Ok(old)
+    }
+
+    // Returns the size of the instruction.
+    fn execute_extension(
+        &mut self,
+        registered: Arc<Registered<C>>,
+        opcode: &Decoded<C>,
+    ) -> Result<usize, ErrorKind> {
+        let mut values = Vec::with_capacity(registered.params.len());
+        for (i, param) in registered.params.iter().enumerate() {
+            if *param == Param::Read {
+                values.push(self.load_argument(i + 1, opcode)?);
             }
-            _ => panic!("Unhandled parameter mode!"),
         }
+
+        let result = registered.extension.lock().unwrap().execute(&values)?;
+        let written = registered.params.iter().position(|p| *p == Param::Write);
+        if let (Some(i), Some(value)) = (written, result) {
+            self.store(i + 1, value, opcode)?;
+        }
+
+        Ok(opcode.size)
     }
 
-    fn load_ptr(&self, pos: usize, opcode: &[u8; 4]) -> usize {
-        let ptr = self.load_argument(pos, opcode);
+    fn load_ptr(&self, pos: usize, opcode: &Decoded<C>) -> Result<usize, ErrorKind> {
+        let ptr = self.load_argument(pos, opcode)?;
+        let ptr = ptr.to_isize().ok_or(ErrorKind::Overflow)?;
 
         if ptr < 0 {
-            panic!("Invalid instruction pointer!")
+            return Err(ErrorKind::InvalidJump(ptr));
         }
-        ptr as usize
+        Ok(ptr as usize)
     }
 
-    pub fn step(&mut self, input: isize) -> State {
-        while self.iptr < self.instructions.len() {
-            let opcode = self.decode_opcode();
+    pub fn step(&mut self, input: C) -> State<C> {
+        self.try_step(input).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-            let stride = match opcode[0] {
-                1 => {
-                    let s1 = self.load_argument(1, &opcode);
-                    let s2 = self.load_argument(2, &opcode);
-                    self.store(3, s1 + s2, &opcode);
-                    4
-                }
-                2 => {
-                    let s1 = self.load_argument(1, &opcode);
-                    let s2 = self.load_argument(2, &opcode);
-                    self.store(3, s1 * s2, &opcode);
-                    4
-                }
-                3 => {
-                    if self.input_requested {
-                        self.store(1, input, &opcode);
-                        self.input_requested = false;
-                        2
-                    } else {
-                        self.input_requested = true;
-                        return State::Input;
+    pub fn try_step(&mut self, input: C) -> Result<State<C>, IntcodeError> {
+        self.execute(input, None).map_err(|kind| self.fault(kind))
+    }
+
+    pub fn try_step_traced(
+        &mut self,
+        input: C,
+        tracer: &mut dyn Tracer<C>,
+    ) -> Result<State<C>, IntcodeError> {
+        self.execute(input, Some(tracer))
+            .map_err(|kind| self.fault(kind))
+    }
+
+    // Runs until the program halts, returning State::Terminated, or the Io runs out of input,
+    // returning State::Input. In the latter case run can be called again to resume once more
+    // input is available.
+    pub fn run(&mut self, io: &mut dyn Io<C>) -> Result<State<C>, IntcodeError> {
+        self.run_with(io, None)
+    }
+
+    // Runs the program to the end and returns the first memory cell. Running out of input is an
+    // error.
+    pub fn evaluate(&mut self, io: &mut dyn Io<C>) -> Result<C, IntcodeError> {
+        if self.run(io)? == State::Input {
+            return Err(self.fault(ErrorKind::InputStarved));
+        }
+        Ok(self.first_cell())
+    }
+
+    pub fn run_traced(
+        &mut self,
+        io: &mut dyn Io<C>,
+        tracer: &mut dyn Tracer<C>,
+    ) -> Result<State<C>, IntcodeError> {
+        self.run_with(io, Some(tracer))
+    }
+
+    fn run_with<'t>(
+        &mut self,
+        io: &mut dyn Io<C>,
+        mut tracer: Option<&mut (dyn Tracer<C> + 't)>,
+    ) -> Result<State<C>, IntcodeError> {
+        loop {
+            let mut input = C::default();
+            if let Some(recorded) = self.recorded_input() {
+                input = recorded;
+            } else if self.input_requested {
+                input = match io.input() {
+                    Ok(input) => input,
+                    Err(InputError::End) => return Ok(State::Input),
+                    Err(InputError::Invalid) => return Err(self.fault(ErrorKind::InvalidInput)),
+                };
+            }
+
+            let state = self
+                .execute(input, tracer.as_deref_mut())
+                .map_err(|kind| self.fault(kind))?;
+
+            match state {
+                State::Input => (),
+                State::Output(o) => io.output(o),
+                State::Terminated => return Ok(State::Terminated),
+            }
+        }
+    }
+
+    fn execute<'t>(
+        &mut self,
+        input: C,
+        mut tracer: Option<&mut (dyn Tracer<C> + 't)>,
+    ) -> Result<State<C>, ErrorKind> {
+        loop {
+            if tracer.is_none() {
+                self.execute_plain();
+            }
+            if let Some(state) = self.execute_instruction(input, tracer.as_deref_mut())? {
+                return Ok(state);
+            }
+        }
+    }
+
+    // Executes the instruction at the instruction pointer. Returns None if it did not perform
+    // any I/O. Input instructions first return State::Input and consume the input on the next
+    // call, like step does.
+    pub fn try_step_instruction(&mut self, input: C) -> Result<Option<State<C>>, IntcodeError> {
+        self.execute_instruction(input, None)
+            .map_err(|kind| self.fault(kind))
+    }
+
+    fn execute_instruction<'t>(
+        &mut self,
+        input: C,
+        tracer: Option<&mut (dyn Tracer<C> + 't)>,
+    ) -> Result<Option<State<C>>, ErrorKind> {
+        if self.halted {
+            return Ok(Some(State::Terminated));
+        }
+        if self.budget == Some(0) {
+            return Err(ErrorKind::BudgetExhausted);
+        }
+
+        let record = match tracer {
+            Some(_) => trace::begin(self),
+            None => None,
+        };
+        self.last_write = None;
+
+        let (iptr, base, input_read) = (self.iptr, self.base, self.input_requested);
+        let state = self.execute_opcode(input)?;
+
+        if state != Some(State::Input) {
+            self.halted = state == Some(State::Terminated);
+            self.retired += 1;
+            if let Some(budget) = self.budget.as_mut() {
+                *budget -= 1;
+            }
+            if let (Some(tracer), Some(mut record)) = (tracer, record) {
+                record.write = self.last_write.map(|(a, _, v)| (a, v));
+                tracer.trace(&record);
+            }
+            if let Some(history) = self.history.as_mut() {
+                history.push(Undo {
+                    iptr,
+                    base,
+                    write: self.last_write.map(|(a, old, _)| (a, old)),
+                    input: input_read,
+                });
+            }
+        }
+
+        if let Some(loops) = self.loops.as_mut() {
+            if state.is_some() {
+                loops.reset();
+            } else if loops.check(&self.memory, self.iptr, self.base) {
+                return Err(ErrorKind::Looping);
+            }
+        }
+
+        Ok(state)
+    }
+
+    // Executes instructions without I/O as long as no budget, history or loop detection needs to
+    // follow every single one. Stops before the first instruction that needs execute_instruction,
+    // which includes I/O, halting, extensions and all faults, so that it reports them.
+    fn execute_plain(&mut self) {
+        if self.halted || self.budget.is_some() || self.history.is_some() || self.loops.is_some() {
+            return;
+        }
+
+        // Kept in locals, the compiler cannot keep fields in registers across the memory calls.
+        let mut iptr = self.iptr;
+        let mut retired = 0;
+        loop {
+            let opcode = match self.cache.get(iptr) {
+                Some(opcode) => *opcode,
+                None => {
+                    self.iptr = iptr;
+                    match self.fetch() {
+                        Ok(opcode) => opcode,
+                        Err(_) => break,
                     }
                 }
-                4 => {
-                    let result = self.load_argument(1, &opcode);
-                    self.iptr += 2;
-                    return State::Output(result);
-                }
-                5 => {
-                    if self.load_argument(1, &opcode) != 0 {
-                        self.iptr = self.load_ptr(2, &opcode);
-                        0
-                    } else {
-                        3
+            };
+            let load = |pos| self.load_argument(pos, &opcode).ok();
+
+            iptr = match opcode.code {
+                1 | 2 | 7 | 8 => {
+                    let (s1, s2) = match (load(1), load(2)) {
+                        (Some(s1), Some(s2)) => (s1, s2),
+                        _ => break,
+                    };
+                    let result = match (opcode.code, self.arithmetic) {
+                        (1, Arithmetic::Wrapping) => Some(s1.wrapping_add(s2)),
+                        (1, Arithmetic::Checked) => s1.checked_add(s2),
+                        (2, Arithmetic::Wrapping) => Some(s1.wrapping_mul(s2)),
+                        (2, Arithmetic::Checked) => s1.checked_mul(s2),
+                        (7, _) => Some(C::from_isize((s1 < s2) as isize)),
+                        _ => Some(C::from_isize((s1 == s2) as isize)),
+                    };
+                    let address = self.address(opcode.operands[2]);
+                    match (result, address) {
+                        (Some(result), Ok(address)) if self.write_cell(address, result).is_ok() => {
+                            iptr + 4
+                        }
+                        _ => break,
                     }
                 }
-                6 => {
-                    if self.load_argument(1, &opcode) == 0 {
-                        self.iptr = self.load_ptr(2, &opcode);
-                        0
-                    } else {
-                        3
+                5 | 6 => match load(1) {
+                    Some(s1) if (s1 == C::default()) == (opcode.code == 6) => {
+                        match self.load_ptr(2, &opcode) {
+                            Ok(target) => target,
+                            Err(_) => break,
+                        }
                     }
-                }
-                7 => {
-                    let s1 = self.load_argument(1, &opcode);
-                    let s2 = self.load_argument(2, &opcode);
+                    Some(_) => iptr + 3,
+                    None => break,
+                },
+                9 => match load(1).map(|offset| self.relative(offset)) {
+                    Some(Ok(base)) => {
+                        self.base = base;
+                        iptr + 2
+                    }
+                    _ => break,
+                },
+                _ => break,
+            };
+            retired += 1;
+        }
 
-                    self.store(3, (s1 < s2) as isize, &opcode);
-                    4
-                }
-                8 => {
-                    let s1 = self.load_argument(1, &opcode);
-                    let s2 = self.load_argument(2, &opcode);
+        self.iptr = iptr;
+        self.retired += retired;
+    }
 
-                    self.store(3, (s1 == s2) as isize, &opcode);
-                    4
-                }
-                9 => {
-                    self.base += self.load_argument(1, &opcode);
+    fn execute_opcode(&mut self, input: C) -> Result<Option<State<C>>, ErrorKind> {
+        let opcode = self.fetch()?;
+
+        let stride = match opcode.code {
+            1 => {
+                let s1 = self.load_argument(1, &opcode)?;
+                let s2 = self.load_argument(2, &opcode)?;
+                let sum = match self.arithmetic {
+                    Arithmetic::Wrapping => s1.wrapping_add(s2),
+                    Arithmetic::Checked => s1.checked_add(s2).ok_or(ErrorKind::Overflow)?,
+                };
+                self.store(3, sum, &opcode)?;
+                4
+            }
+            2 => {
+                let s1 = self.load_argument(1, &opcode)?;
+                let s2 = self.load_argument(2, &opcode)?;
+                let product = match self.arithmetic {
+                    Arithmetic::Wrapping => s1.wrapping_mul(s2),
+                    Arithmetic::Checked => s1.checked_mul(s2).ok_or(ErrorKind::Overflow)?,
+                };
+                self.store(3, product, &opcode)?;
+                4
+            }
+            3 => {
+                if self.input_requested {
+                    let input = match &self.tap {
+                        Some((tap, machine)) => {
+                            tap.lock().unwrap().input(*machine, self.retired, input)?
+                        }
+                        None => input,
+                    };
+                    self.store(1, input, &opcode)?;
+                    self.input_requested = false;
                     2
+                } else {
+                    self.input_requested = true;
+                    return Ok(Some(State::Input));
                 }
-                99 => return State::Terminated,
-                o => panic!("Unhandled opcode {}", o),
-            };
+            }
+            4 => {
+                let result = self.load_argument(1, &opcode)?;
+                if let Some((tap, machine)) = &self.tap {
+                    tap.lock().unwrap().output(*machine, self.retired, result)?;
+                }
+                self.iptr += 2;
+                return Ok(Some(State::Output(result)));
+            }
+            5 => {
+                if self.load_argument(1, &opcode)? != C::default() {
+                    self.iptr = self.load_ptr(2, &opcode)?;
+                    0
+                } else {
+                    3
+                }
+            }
+            6 => {
+                if self.load_argument(1, &opcode)? == C::default() {
+                    self.iptr = self.load_ptr(2, &opcode)?;
+                    0
+                } else {
+                    3
+                }
+            }
+            7 => {
+                let s1 = self.load_argument(1, &opcode)?;
+                let s2 = self.load_argument(2, &opcode)?;
 
-            self.iptr += stride;
-        }
+                self.store(3, C::from_isize((s1 < s2) as isize), &opcode)?;
+                4
+            }
+            8 => {
+                let s1 = self.load_argument(1, &opcode)?;
+                let s2 = self.load_argument(2, &opcode)?;
+
+                self.store(3, C::from_isize((s1 == s2) as isize), &opcode)?;
+                4
+            }
+            9 => {
+                self.base = self.relative(self.load_argument(1, &opcode)?)?;
+                2
+            }
+            99 => return Ok(Some(State::Terminated)),
+            code => match self.extensions.get(&code) {
+                Some(registered) => self.execute_extension(registered.clone(), &opcode)?,
+                None => return Err(ErrorKind::InvalidOpcode),
+            },
+        };
 
-        panic!("Reached end of memory!");
+        self.iptr += stride;
+        Ok(None)
     }
 }
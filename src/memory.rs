@@ -0,0 +1,290 @@
+use crate::{Cell, ErrorKind};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[test]
+fn test_grow_on_demand() {
+    let mut memory = Memory::new(vec![1, 2, 3], DEFAULT_MEMORY_LIMIT);
+    assert_eq!(3, memory.high_water_mark());
+    assert_eq!(Ok(0), memory.get(50_000));
+
+    assert_eq!(Ok(0), memory.set(50_000, 7));
+    assert_eq!(Ok(7), memory.get(50_000));
+    assert_eq!(Ok(2), memory.get(1));
+    assert_eq!(50_001, memory.high_water_mark());
+    assert_eq!(2 * PAGE_SIZE, memory.allocated_cells());
+}
+
+#[test]
+fn test_sparse() {
+    let mut memory = Memory::new(vec![], DEFAULT_MEMORY_LIMIT);
+    let far = 1 << 31;
+
+    memory.set(far, -1).unwrap();
+    assert_eq!(Ok(-1), memory.get(far));
+    assert_eq!(Ok(0), memory.get(far + 1));
+    assert_eq!(far + 1, memory.high_water_mark());
+    assert_eq!(PAGE_SIZE, memory.allocated_cells());
+}
+
+#[test]
+fn test_segments() {
+    let mut memory = Memory::new(vec![1, 2, 0, 3, 0, 0], DEFAULT_MEMORY_LIMIT);
+    memory.set(5000, 0).unwrap();
+    memory.set(1 << 31, 4).unwrap();
+
+    assert_eq!(
+        vec![(0, &[1, 2, 0, 3][..]), (1 << 31, &[4][..])],
+        memory.segments()
+    );
+}
+
+#[test]
+fn test_copy_on_write() {
+    let mut memory = Memory::new(vec![1; 2 * PAGE_SIZE], DEFAULT_MEMORY_LIMIT);
+    assert_eq!(0, memory.clone().shared_cells());
+    let mut copy = memory.fork();
+    assert_eq!(2 * PAGE_SIZE, copy.shared_cells());
+
+    copy.set(PAGE_SIZE, 5).unwrap();
+    assert_eq!(PAGE_SIZE, copy.shared_cells());
+    assert_eq!(Ok(5), copy.get(PAGE_SIZE));
+    assert_eq!(Ok(1), memory.get(PAGE_SIZE));
+
+    memory.set(0, 3).unwrap();
+    assert_eq!(0, memory.shared_cells());
+    assert_eq!(Ok(1), copy.get(0));
+}
+
+#[test]
+fn test_limit() {
+    let mut memory = Memory::new(vec![0; 10], 16);
+
+    assert_eq!(Ok(0), memory.get(15));
+    assert_eq!(Err(ErrorKind::MemoryLimit(16)), memory.get(16));
+    assert_eq!(Err(ErrorKind::MemoryLimit(20)), memory.set(20, 1));
+    assert_eq!(10, memory.high_water_mark());
+}
+
+#[test]
+#[should_panic(expected = "Program of 17 cells exceeds the memory limit of 16")]
+fn test_program_over_limit() {
+    Memory::new(vec![0; 17], 16);
+}
+
+const PAGE_BITS: usize = 10;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+
+// Pages below this index are kept in a vector, all others in a hash map.
+const DENSE_PAGES: usize = 1024;
+
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 32;
+
+// Pages are shared between forks of a memory until one of them writes to the page. Writes to
+// owned pages do not need to check the reference count, which is costly on every write.
+#[derive(Clone)]
+enum Page<C> {
+    Owned(Box<[C; PAGE_SIZE]>),
+    Shared(Arc<[C; PAGE_SIZE]>),
+}
+
+impl<C: Cell> Page<C> {
+    fn new() -> Page<C> {
+        Page::Owned(Box::new([C::default(); PAGE_SIZE]))
+    }
+
+    fn cells(&self) -> &[C; PAGE_SIZE] {
+        match self {
+            Page::Owned(cells) => cells,
+            Page::Shared(cells) => cells,
+        }
+    }
+
+    fn cells_mut(&mut self) -> &mut [C; PAGE_SIZE] {
+        if let Page::Shared(cells) = self {
+            *self = Page::Owned(Box::new(**cells));
+        }
+        match self {
+            Page::Owned(cells) => cells,
+            Page::Shared(_) => unreachable!(),
+        }
+    }
+
+    fn share(&mut self) {
+        if let Page::Owned(cells) = self {
+            *self = Page::Shared(Arc::new(**cells));
+        }
+    }
+
+    fn is_shared(&self) -> bool {
+        match self {
+            Page::Owned(_) => false,
+            Page::Shared(cells) => Arc::strong_count(cells) > 1,
+        }
+    }
+}
+
+// Memory of an Intcode machine. Cells are allocated in pages on first write, reading a cell that
+// was never written yields zero. Accesses at or beyond the limit fail. Clones copy the pages,
+// forks share them until one of them writes to a page.
+#[derive(Clone)]
+pub struct Memory<C: Cell = isize> {
+    dense: Vec<Option<Page<C>>>,
+    sparse: HashMap<usize, Page<C>>,
+    limit: usize,
+    high_water: usize,
+}
+
+impl Memory {
+    pub fn new(program: Vec<isize>, limit: usize) -> Memory {
+        Memory::from_cells(program, limit)
+    }
+}
+
+impl<C: Cell> Memory<C> {
+    // Panics if the program does not fit below the limit.
+    pub fn from_cells(program: Vec<C>, limit: usize) -> Memory<C> {
+        let mut memory = Memory {
+            dense: Vec::new(),
+            sparse: HashMap::new(),
+            limit,
+            high_water: 0,
+        };
+
+        assert!(
+            program.len() <= limit,
+            "Program of {} cells exceeds the memory limit of {}",
+            program.len(),
+            limit
+        );
+        for (address, value) in program.into_iter().enumerate() {
+            memory.set(address, value).unwrap();
+        }
+
+        memory
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    // One past the highest address that was ever written, including the program itself.
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water
+    }
+
+    pub(crate) fn set_high_water_mark(&mut self, high_water: usize) {
+        self.high_water = high_water;
+    }
+
+    // Duplicates the memory, both share all pages until either of them writes to it.
+    pub fn fork(&mut self) -> Memory<C> {
+        self.share();
+        self.clone()
+    }
+
+    // Lets clones share all pages from now on.
+    pub(crate) fn share(&mut self) {
+        for page in self.dense.iter_mut().flatten() {
+            page.share();
+        }
+        for page in self.sparse.values_mut() {
+            page.share();
+        }
+    }
+
+    // Allocated pages ordered by address, trailing zeros are omitted.
+    pub fn segments(&self) -> Vec<(usize, &[C])> {
+        let dense = self
+            .dense
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| p.as_ref().map(|p| (i, p)));
+        let mut sparse: Vec<_> = self.sparse.iter().map(|(i, p)| (*i, p)).collect();
+        sparse.sort_by_key(|(i, _)| *i);
+
+        dense
+            .chain(sparse)
+            .filter_map(|(i, p)| {
+                let cells = p.cells();
+                let len = cells.iter().rposition(|&v| v != C::default())? + 1;
+                Some((i << PAGE_BITS, &cells[..len]))
+            })
+            .collect()
+    }
+
+    pub fn allocated_cells(&self) -> usize {
+        let dense = self.dense.iter().filter(|p| p.is_some()).count();
+        (dense + self.sparse.len()) * PAGE_SIZE
+    }
+
+    // Number of allocated cells in pages that are shared with a clone of this memory.
+    pub fn shared_cells(&self) -> usize {
+        let dense = self.dense.iter().flatten();
+        let shared = dense
+            .chain(self.sparse.values())
+            .filter(|p| p.is_shared())
+            .count();
+        shared * PAGE_SIZE
+    }
+
+    pub fn get(&self, address: usize) -> Result<C, ErrorKind> {
+        match self.dense.get(address >> PAGE_BITS) {
+            Some(Some(page)) if address < self.limit => Ok(page.cells()[address & (PAGE_SIZE - 1)]),
+            _ => self.get_slow(address),
+        }
+    }
+
+    // Everything but cells of allocated pages in the dense range, kept out of line so that get
+    // itself stays small enough to be inlined.
+    #[cold]
+    fn get_slow(&self, address: usize) -> Result<C, ErrorKind> {
+        if address >= self.limit {
+            return Err(ErrorKind::MemoryLimit(address));
+        }
+
+        let page = address >> PAGE_BITS;
+        let page = if page < DENSE_PAGES {
+            self.dense.get(page).and_then(|p| p.as_ref())
+        } else {
+            self.sparse.get(&page)
+        };
+
+        Ok(page.map_or(C::default(), |p| p.cells()[address & (PAGE_SIZE - 1)]))
+    }
+
+    // Returns the previous value of the cell.
+    pub fn set(&mut self, address: usize, value: C) -> Result<C, ErrorKind> {
+        if let Some(Some(Page::Owned(cells))) = self.dense.get_mut(address >> PAGE_BITS) {
+            if address < self.limit {
+                let old = std::mem::replace(&mut cells[address & (PAGE_SIZE - 1)], value);
+                self.high_water = std::cmp::max(self.high_water, address + 1);
+                return Ok(old);
+            }
+        }
+        self.set_slow(address, value)
+    }
+
+    // Like get_slow for writes, this includes the first write to a shared page.
+    #[cold]
+    fn set_slow(&mut self, address: usize, value: C) -> Result<C, ErrorKind> {
+        if address >= self.limit {
+            return Err(ErrorKind::MemoryLimit(address));
+        }
+
+        let page = address >> PAGE_BITS;
+        let page = if page < DENSE_PAGES {
+            if self.dense.len() <= page {
+                self.dense.resize_with(page + 1, || None);
+            }
+            self.dense[page].get_or_insert_with(Page::new)
+        } else {
+            self.sparse.entry(page).or_insert_with(Page::new)
+        };
+
+        let cell = &mut page.cells_mut()[address & (PAGE_SIZE - 1)];
+        let old = std::mem::replace(cell, value);
+        self.high_water = std::cmp::max(self.high_water, address + 1);
+        Ok(old)
+    }
+}
@@ -0,0 +1,204 @@
+use crate::disasm::Op;
+use crate::trace::{TraceRecord, Tracer};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+#[test]
+fn test_profile() {
+    // Counts down from 3 and outputs every value.
+    let program = crate::asm::assemble(
+        "
+                IN [n]
+        loop:   OUT [n]
+                ADD [n], #-1, [n]
+                JNZ [n], #loop
+                HLT
+        n:      .data 0
+        ",
+    )
+    .unwrap();
+
+    let mut profiler = Profiler::new();
+    let mut interpreter = crate::Intcode::new(program);
+    let mut io = crate::BufIo::new(&[3]);
+    interpreter.run_traced(&mut io, &mut profiler).unwrap();
+
+    assert_eq!(11, profiler.total());
+    assert_eq!((1, 3), (profiler.inputs(), profiler.outputs()));
+    assert_eq!(3, profiler.count(2));
+    assert_eq!(1, profiler.count(0));
+    assert_eq!(3, profiler.op_count(Op::Jnz));
+    assert_eq!(
+        vec![Loop {
+            start: 2,
+            end: 8,
+            iterations: 2,
+            instructions: 9
+        }],
+        profiler.hot_loops()
+    );
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Loop {
+    pub start: usize,
+    // Address of the jump that closes the loop.
+    pub end: usize,
+    // Number of times the backward jump was taken.
+    pub iterations: u64,
+    // Instructions executed at addresses within the loop.
+    pub instructions: u64,
+}
+
+// Collects execution counts per address and per opcode. Taken backward jumps are
+// counted as loops.
+#[derive(Default)]
+pub struct Profiler {
+    total: u64,
+    inputs: u64,
+    outputs: u64,
+    addresses: HashMap<usize, (u64, isize)>,
+    ops: HashMap<Option<Op>, u64>,
+    back_edges: HashMap<(usize, usize), u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn inputs(&self) -> u64 {
+        self.inputs
+    }
+
+    pub fn outputs(&self) -> u64 {
+        self.outputs
+    }
+
+    pub fn count(&self, address: usize) -> u64 {
+        self.addresses.get(&address).map_or(0, |c| c.0)
+    }
+
+    pub fn op_count(&self, op: Op) -> u64 {
+        self.ops.get(&Some(op)).copied().unwrap_or(0)
+    }
+
+    pub fn hot_loops(&self) -> Vec<Loop> {
+        let mut loops: Vec<_> = self
+            .back_edges
+            .iter()
+            .map(|(&(end, start), &iterations)| Loop {
+                start,
+                end,
+                iterations,
+                instructions: self
+                    .addresses
+                    .iter()
+                    .filter(|(a, _)| (start..=end).contains(*a))
+                    .map(|(_, c)| c.0)
+                    .sum(),
+            })
+            .collect();
+
+        loops.sort_by(|a, b| {
+            b.instructions
+                .cmp(&a.instructions)
+                .then(a.start.cmp(&b.start))
+        });
+        loops
+    }
+
+    pub fn report(&self, top: usize) -> String {
+        let mut report = String::new();
+        let percent = |n: u64| 100.0 * n as f64 / std::cmp::max(self.total, 1) as f64;
+
+        writeln!(report, "Instructions retired: {}", self.total).unwrap();
+        writeln!(report, "Inputs: {}, outputs: {}", self.inputs, self.outputs).unwrap();
+
+        writeln!(report, "\nPer opcode:").unwrap();
+        let mut ops: Vec<_> = self.ops.iter().collect();
+        ops.sort_by_key(|(op, count)| (std::cmp::Reverse(**count), op.map(|op| op.code())));
+        for (op, count) in ops {
+            let name = op.map_or("???", |op| op.name());
+            writeln!(
+                report,
+                "  {:<4} {:>12} {:>6.2}%",
+                name,
+                count,
+                percent(*count)
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nHottest addresses:").unwrap();
+        let mut addresses: Vec<_> = self.addresses.iter().collect();
+        addresses.sort_by(|a, b| (b.1).0.cmp(&(a.1).0).then(a.0.cmp(b.0)));
+        for (address, (count, opcode)) in addresses.into_iter().take(top) {
+            let name = Op::from_code(opcode % 100).map_or("???", |op| op.name());
+            writeln!(
+                report,
+                "  {:>6}: {:<4} {:>12} {:>6.2}%",
+                address,
+                name,
+                count,
+                percent(*count)
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nHot loops:").unwrap();
+        for l in self.hot_loops().into_iter().take(top) {
+            writeln!(
+                report,
+                "  {:>6} - {:<6} {:>10} iterations {:>12} instructions {:>6.2}%",
+                l.start,
+                l.end,
+                l.iterations,
+                l.instructions,
+                percent(l.instructions)
+            )
+            .unwrap();
+        }
+
+        report
+    }
+}
+
+impl Tracer for Profiler {
+    fn trace(&mut self, record: &TraceRecord) {
+        self.total += 1;
+
+        let entry = self.addresses.entry(record.address).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 = record.opcode;
+        *self.ops.entry(record.op).or_insert(0) += 1;
+
+        let value = |i: usize| record.operands.get(i).and_then(|o| o.value);
+        let taken = match record.op {
+            Some(Op::In) => {
+                self.inputs += 1;
+                None
+            }
+            Some(Op::Out) => {
+                self.outputs += 1;
+                None
+            }
+            Some(Op::Jnz) => value(0).filter(|&v| v != 0).and(value(1)),
+            Some(Op::Jz) => value(0).filter(|&v| v == 0).and(value(1)),
+            _ => None,
+        };
+
+        if let Some(target) = taken {
+            if target >= 0 && target as usize <= record.address {
+                *self
+                    .back_edges
+                    .entry((record.address, target as usize))
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,368 @@
+// Records the values a session reads and writes, and replays them later. Every value is logged
+// with the machine and its step, the number of instructions the machine had retired, as a line
+// `<machine> <step> in <value>` or `<machine> <step> out <value>`. Machines are numbered in the
+// order the tap was attached to them, and replayed independently of each other, as replaying
+// changes when machines request input and with it the order in which a scheduler runs them.
+// A machine that steps back logs `<machine> <step> back`, which drops its values from that step
+// on, as the machine reads and writes them again.
+//
+// While replaying, machines read the recorded inputs instead of the ones they are given, until
+// the recording is exhausted and the session continues live. Runs driven by an Io do not ask it
+// for inputs that are replayed, interactive callers check Intcode::recorded_input for the same.
+// Outputs, and the steps at which values are read and written, must match the recording,
+// otherwise the machine fails with ErrorKind::Diverged.
+//
+// The binaries attach the tap of their --record or --replay option to the machines they create
+// with cli::Args::machine.
+
+use crate::ErrorKind;
+use std::fmt;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn test_record_replay() {
+    // Outputs the sum of two inputs.
+    let program = vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0];
+
+    let recorder = Arc::new(Mutex::new(Recorder::new(Vec::new())));
+    let mut interpreter = crate::Intcode::new(program.clone());
+    interpreter.set_tap(recorder.clone(), 0);
+    let mut io = crate::BufIo::new(&[4, 5]);
+    interpreter.run(&mut io).unwrap();
+
+    let log = String::from_utf8(recorder.lock().unwrap().get_ref().clone()).unwrap();
+    assert_eq!("0 0 in 4\n0 1 in 5\n0 3 out 9\n", log);
+
+    let mut interpreter = crate::Intcode::new(program.clone());
+    interpreter.set_tap(Arc::new(Mutex::new(Replay::parse(&log).unwrap())), 0);
+    let mut io = crate::BufIo::new(&[]);
+    assert_eq!(Ok(crate::State::Terminated), interpreter.run(&mut io));
+    assert_eq!(&vec![9], io.output());
+
+    // Replays the first input, the second one is read live.
+    let replay = Arc::new(Mutex::new(Replay::parse("1 0 in 7\n").unwrap()));
+    let mut interpreter = crate::Intcode::new(program.clone());
+    interpreter.set_tap(replay.clone(), 1);
+    assert_eq!(crate::State::Input, interpreter.step(0));
+    assert_eq!(Some(7), interpreter.recorded_input());
+    assert_eq!(crate::State::Input, interpreter.step(0));
+    assert_eq!(None, interpreter.recorded_input());
+    assert_eq!(crate::State::Output(8), interpreter.step(1));
+    assert_eq!(0, replay.lock().unwrap().remaining());
+
+    let mut interpreter = crate::Intcode::new(program);
+    let replay = Replay::parse(&log.replace("9", "8")).unwrap();
+    interpreter.set_tap(Arc::new(Mutex::new(replay)), 0);
+    let error = interpreter.run(&mut crate::BufIo::new(&[])).unwrap_err();
+    assert_eq!((ErrorKind::Diverged, 8), (error.kind, error.iptr));
+}
+
+#[test]
+fn test_fork() {
+    let program = vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0];
+    let session = |tap: SharedTap, inputs: [isize; 3]| {
+        let mut interpreter = crate::Intcode::new(program.clone());
+        interpreter.attach_tap(tap);
+        interpreter.step(0);
+        interpreter.step(inputs[0]);
+        let mut fork = interpreter.fork();
+        (fork.step(inputs[1]), interpreter.step(inputs[2]))
+    };
+
+    let recorder = Arc::new(Mutex::new(Recorder::new(Vec::new())));
+    let outputs = session(recorder.clone(), [4, 5, 6]);
+    assert_eq!((crate::State::Output(9), crate::State::Output(10)), outputs);
+
+    let log = String::from_utf8(recorder.lock().unwrap().get_ref().clone()).unwrap();
+    assert_eq!("0 0 in 4\n1 1 in 5\n1 3 out 9\n0 1 in 6\n0 3 out 10\n", log);
+
+    let replay = Arc::new(Mutex::new(Replay::parse(&log).unwrap()));
+    assert_eq!(outputs, session(replay.clone(), [0, 0, 0]));
+    assert_eq!(0, replay.lock().unwrap().remaining());
+}
+
+#[test]
+fn test_step_back() {
+    let program = vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0];
+
+    // The second input and the output depending on it are taken back.
+    let recorder = Arc::new(Mutex::new(Recorder::new(Vec::new())));
+    let mut interpreter = crate::Intcode::new(program.clone());
+    interpreter.set_history(Some(10));
+    interpreter.set_tap(recorder.clone(), 0);
+    interpreter.step(0);
+    interpreter.step(4);
+    assert_eq!(crate::State::Output(9), interpreter.step(5));
+    assert_eq!(Some(3), interpreter.rewind_to_input());
+    assert_eq!(crate::State::Input, interpreter.step(0));
+    assert_eq!(crate::State::Output(10), interpreter.step(6));
+
+    let log = String::from_utf8(recorder.lock().unwrap().get_ref().clone()).unwrap();
+    assert_eq!(
+        "0 0 in 4\n0 1 in 5\n0 3 out 9\n0 1 back\n0 1 in 6\n0 3 out 10\n",
+        log
+    );
+
+    let replay = Arc::new(Mutex::new(Replay::parse(&log).unwrap()));
+    let mut interpreter = crate::Intcode::new(program);
+    interpreter.set_history(Some(10));
+    interpreter.set_tap(replay.clone(), 0);
+    let mut io = crate::BufIo::new(&[]);
+    assert_eq!(Ok(crate::State::Terminated), interpreter.run(&mut io));
+    assert_eq!(&vec![10], io.output());
+
+    // Stepping back while replaying replays the values again.
+    assert_eq!(Some(4), interpreter.rewind_to_input());
+    assert_eq!(2, replay.lock().unwrap().remaining());
+    assert_eq!(Ok(crate::State::Terminated), interpreter.run(&mut io));
+    assert_eq!(&vec![10, 10], io.output());
+}
+
+#[test]
+fn test_parse() {
+    let replay = Replay::parse("1 0 in -1\n\n1 12 out 5\n0 3 out 1").unwrap();
+    assert_eq!(vec![(3, Direction::Out, 1)], replay.machines[0]);
+    assert_eq!(
+        vec![(0, Direction::In, -1), (12, Direction::Out, 5)],
+        replay.machines[1]
+    );
+    assert_eq!(3, replay.remaining());
+    assert_eq!(
+        Err(ParseError { line: 2 }),
+        Replay::parse("0 0 in -1\n0 3 inout 5").map(|_| ())
+    );
+
+    let replay = Replay::parse("0 0 in 1\n1 0 in 2\n0 2 out 3\n0 1 back\n0 1 in 4").unwrap();
+    assert_eq!(
+        vec![(0, Direction::In, 1), (1, Direction::In, 4)],
+        replay.machines[0]
+    );
+}
+
+// Sees every value a machine reads and writes, with the number of instructions it had retired.
+pub trait Tap<C = isize>: Send {
+    // Returns the number of a machine the tap is attached to for the first time.
+    fn attach(&mut self) -> usize;
+
+    // The input the machine reads at the step while replaying.
+    fn recorded_input(&self, machine: usize, step: u64) -> Option<C>;
+
+    // Called with the input the machine was given. Returns the value it reads instead.
+    fn input(&mut self, machine: usize, step: u64, value: C) -> Result<C, ErrorKind>;
+
+    fn output(&mut self, machine: usize, step: u64, value: C) -> Result<(), ErrorKind>;
+
+    // Called when the machine stepped back to the step, it reads and writes the values from
+    // there on again.
+    fn step_back(&mut self, machine: usize, step: u64);
+}
+
+pub type SharedTap<C = isize> = Arc<Mutex<dyn Tap<C>>>;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+pub struct Recorder<W> {
+    writer: W,
+    machines: usize,
+    // A write failed where no error could be returned, the next value fails to log.
+    failed: bool,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(writer: W) -> Recorder<W> {
+        Recorder {
+            writer,
+            machines: 0,
+            failed: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    fn log(
+        &mut self,
+        machine: usize,
+        step: u64,
+        direction: &str,
+        value: isize,
+    ) -> Result<(), ErrorKind> {
+        if self.failed {
+            return Err(ErrorKind::RecordingFailed);
+        }
+        writeln!(self.writer, "{} {} {} {}", machine, step, direction, value)
+            .map_err(|_| ErrorKind::RecordingFailed)
+    }
+}
+
+impl<W: Write + Send> Tap for Recorder<W> {
+    fn attach(&mut self) -> usize {
+        self.machines += 1;
+        self.machines - 1
+    }
+
+    fn recorded_input(&self, _machine: usize, _step: u64) -> Option<isize> {
+        None
+    }
+
+    fn input(&mut self, machine: usize, step: u64, value: isize) -> Result<isize, ErrorKind> {
+        self.log(machine, step, "in", value)?;
+        Ok(value)
+    }
+
+    fn output(&mut self, machine: usize, step: u64, value: isize) -> Result<(), ErrorKind> {
+        self.log(machine, step, "out", value)
+    }
+
+    fn step_back(&mut self, machine: usize, step: u64) {
+        self.failed |= writeln!(self.writer, "{} {} back", machine, step).is_err();
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ParseError {
+    pub line: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid recording in line {}", self.line)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+type Entry = (u64, Direction, isize);
+
+pub struct Replay {
+    // The recorded values of each machine.
+    machines: Vec<Vec<Entry>>,
+    // Index of the next value to replay for each machine.
+    next: Vec<usize>,
+    attached: usize,
+}
+
+fn parse_entry(line: &str) -> Option<(usize, Entry)> {
+    let parts: Vec<_> = line.split_whitespace().collect();
+    if let [machine, step, direction, value] = parts[..] {
+        let direction = match direction {
+            "in" => Direction::In,
+            "out" => Direction::Out,
+            _ => return None,
+        };
+        Some((
+            machine.parse().ok()?,
+            (step.parse().ok()?, direction, value.parse().ok()?),
+        ))
+    } else {
+        None
+    }
+}
+
+// Parses `<machine> <step> back`.
+fn parse_back(line: &str) -> Option<(usize, u64)> {
+    let parts: Vec<_> = line.split_whitespace().collect();
+    match parts[..] {
+        [machine, step, "back"] => Some((machine.parse().ok()?, step.parse().ok()?)),
+        _ => None,
+    }
+}
+
+impl Replay {
+    pub fn parse(recording: &str) -> Result<Replay, ParseError> {
+        let mut machines: Vec<Vec<Entry>> = Vec::new();
+        for (i, line) in recording.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some((machine, step)) = parse_back(line) {
+                if let Some(entries) = machines.get_mut(machine) {
+                    entries.retain(|&(s, _, _)| s < step);
+                }
+                continue;
+            }
+            let (machine, entry) = parse_entry(line).ok_or(ParseError { line: i + 1 })?;
+            if machines.len() <= machine {
+                machines.resize(machine + 1, Vec::new());
+            }
+            machines[machine].push(entry);
+        }
+
+        let next = vec![0; machines.len()];
+        Ok(Replay {
+            machines,
+            next,
+            attached: 0,
+        })
+    }
+
+    // Number of recorded values that have not been replayed yet.
+    pub fn remaining(&self) -> usize {
+        let total: usize = self.machines.iter().map(Vec::len).sum();
+        total - self.next.iter().sum::<usize>()
+    }
+
+    fn next_entry(&self, machine: usize) -> Option<&Entry> {
+        self.machines
+            .get(machine)
+            .and_then(|entries| entries.get(self.next[machine]))
+    }
+
+    // Checks the next recorded value of the machine and moves past it, unless the recording is
+    // exhausted.
+    fn expect(
+        &mut self,
+        machine: usize,
+        step: u64,
+        direction: Direction,
+    ) -> Result<Option<isize>, ErrorKind> {
+        match self.next_entry(machine) {
+            None => Ok(None),
+            Some(&(s, d, value)) if (s, d) == (step, direction) => {
+                self.next[machine] += 1;
+                Ok(Some(value))
+            }
+            Some(_) => Err(ErrorKind::Diverged),
+        }
+    }
+}
+
+impl Tap for Replay {
+    fn attach(&mut self) -> usize {
+        self.attached += 1;
+        self.attached - 1
+    }
+
+    fn recorded_input(&self, machine: usize, step: u64) -> Option<isize> {
+        match self.next_entry(machine) {
+            Some(&(s, Direction::In, value)) if s == step => Some(value),
+            _ => None,
+        }
+    }
+
+    fn input(&mut self, machine: usize, step: u64, value: isize) -> Result<isize, ErrorKind> {
+        Ok(self.expect(machine, step, Direction::In)?.unwrap_or(value))
+    }
+
+    fn output(&mut self, machine: usize, step: u64, value: isize) -> Result<(), ErrorKind> {
+        match self.expect(machine, step, Direction::Out)? {
+            Some(recorded) if recorded != value => Err(ErrorKind::Diverged),
+            _ => Ok(()),
+        }
+    }
+
+    fn step_back(&mut self, machine: usize, step: u64) {
+        if let Some(entries) = self.machines.get(machine) {
+            let next = &mut self.next[machine];
+            while *next > 0 && entries[*next - 1].0 >= step {
+                *next -= 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,391 @@
+// Runs a network of machines on a single thread. Every machine has a queue of input values.
+// Outputs are grouped into messages of a fixed number of values and delivered to other
+// machines, either along links or as decided by a routing function; messages without a
+// destination, or addressed to a machine that does not exist, are returned to the caller.
+//
+// A machine requesting input while its queue is empty blocks, unless an idle input is set, which
+// it then reads instead, like the network of day 23 reads -1. The network is idle once every
+// running machine in turn polled an empty queue without producing output.
+
+use crate::{Intcode, IntcodeError, State};
+use std::collections::VecDeque;
+use std::fmt;
+
+#[test]
+fn test_links() {
+    // Adds the phase to every input, until it reads a zero.
+    let program = crate::asm::assemble(
+        "
+                IN [phase]
+        loop:   IN [value]
+                JZ [value], #end
+                ADD [value], [phase], [value]
+                OUT [value]
+                JNZ #1, #loop
+        end:    OUT #0
+                HLT
+        phase:  .data 0
+        value:  .data 0
+        ",
+    )
+    .unwrap();
+
+    for policy in [Policy::RoundRobin, Policy::UntilBlocked].iter() {
+        let mut scheduler = Scheduler::new(*policy);
+        for phase in 1..=3 {
+            let machine = scheduler.add(Intcode::new(program.clone()));
+            scheduler.send(machine, &[phase]);
+        }
+        scheduler.link(0, 1);
+        scheduler.link(1, 2);
+        scheduler.send(0, &[10, 20, 0]);
+
+        let mut events = Vec::new();
+        loop {
+            match scheduler.run().unwrap() {
+                Event::Halted => break,
+                event => events.push(event),
+            }
+        }
+
+        let outputs: Vec<_> = events
+            .iter()
+            .filter(|e| matches!(e, Event::Output(..)))
+            .collect();
+        assert_eq!(
+            vec![
+                &Event::Output(2, vec![16]),
+                &Event::Output(2, vec![26]),
+                &Event::Output(2, vec![0])
+            ],
+            outputs
+        );
+        assert_eq!(6, events.len());
+    }
+}
+
+#[test]
+fn test_router() {
+    // Forwards (address, value) packets after adding its own address, polls with -1.
+    let program = crate::asm::assemble(
+        "
+                IN [self]
+        loop:   IN [to]
+                EQ [to], #-1, [c]
+                JNZ [c], #loop
+                IN [value]
+                ADD [value], [self], [value]
+                OUT [to]
+                OUT [value]
+                JNZ #1, #loop
+        self:   .data 0
+        to:     .data 0
+        value:  .data 0
+        c:      .data 0
+        ",
+    )
+    .unwrap();
+
+    let mut scheduler = Scheduler::new(Policy::UntilBlocked);
+    scheduler.set_idle_input(Some(-1));
+    scheduler.set_router(2, |_, packet| match packet[0] {
+        99 => Route::External,
+        to => Route::To(to as usize, vec![99, packet[1]]),
+    });
+    for i in 0..3 {
+        let machine = scheduler.add(Intcode::new(program.clone()));
+        scheduler.send(machine, &[i]);
+    }
+
+    assert_eq!(Ok(Event::Idle), scheduler.run());
+    scheduler.send(1, &[2, 100]);
+    assert_eq!(Ok(Event::Output(2, vec![99, 103])), scheduler.run());
+    assert_eq!(Ok(Event::Idle), scheduler.run());
+
+    scheduler.send(0, &[3, 100]);
+    assert_eq!(
+        Ok(Event::Undeliverable(0, 3, vec![99, 100])),
+        scheduler.run()
+    );
+    // Negative addresses wrap around to indices past the end.
+    scheduler.send(2, &[-2, 100]);
+    assert_eq!(
+        Ok(Event::Undeliverable(2, usize::MAX - 1, vec![99, 102])),
+        scheduler.run()
+    );
+    assert_eq!(Ok(Event::Idle), scheduler.run());
+}
+
+#[test]
+fn test_replayed_input() {
+    use crate::recording::Replay;
+    use std::sync::{Arc, Mutex};
+
+    // Outputs twice its input. Nothing is sent to it, the input comes from the recording.
+    let program = vec![3, 9, 102, 2, 9, 9, 4, 9, 99, 0];
+    let mut interpreter = Intcode::new(program);
+    let replay = Replay::parse("0 0 in 21\n").unwrap();
+    interpreter.set_tap(Arc::new(Mutex::new(replay)), 0);
+
+    let mut scheduler = Scheduler::new(Policy::UntilBlocked);
+    scheduler.add(interpreter);
+    assert_eq!(Ok(Event::Output(0, vec![42])), scheduler.run());
+    assert_eq!(Ok(Event::Terminated(0)), scheduler.run());
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Policy {
+    // Machines take turns, each running until its next input or output.
+    RoundRobin,
+    // A machine keeps running until it blocks on input or halts.
+    UntilBlocked,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Route {
+    // Appends the values to the input queue of the machine.
+    To(usize, Vec<isize>),
+    // Returns the message to the caller of run.
+    External,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Event {
+    // A message from the machine without a destination.
+    Output(usize, Vec<isize>),
+    // No machine makes progress without new input.
+    Idle,
+    Terminated(usize),
+    // A message from the first machine routed to the second one, which does not exist.
+    Undeliverable(usize, usize, Vec<isize>),
+    // All machines have terminated.
+    Halted,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct MachineError {
+    pub machine: usize,
+    pub error: IntcodeError,
+}
+
+impl fmt::Display for MachineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Machine {}: {}", self.machine, self.error)
+    }
+}
+
+impl std::error::Error for MachineError {}
+
+struct Machine {
+    interpreter: Intcode,
+    inputs: VecDeque<isize>,
+    // Outputs of the message that is not complete yet.
+    outputs: Vec<isize>,
+    link: Option<usize>,
+    waiting: bool,
+    terminated: bool,
+}
+
+// Result of a single step of a machine.
+enum Step {
+    // The machine polled an empty queue and did not produce output.
+    Quiet,
+    Active,
+    Event(Event),
+}
+
+type Router = Box<dyn FnMut(usize, &[isize]) -> Route>;
+
+pub struct Scheduler {
+    machines: Vec<Machine>,
+    policy: Policy,
+    idle_input: Option<isize>,
+    router: Option<Router>,
+    message_len: usize,
+    current: usize,
+    // Machines that have not terminated.
+    running: usize,
+    // Consecutive turns in which no machine made progress.
+    quiet: usize,
+}
+
+impl Scheduler {
+    pub fn new(policy: Policy) -> Scheduler {
+        Scheduler {
+            machines: Vec::new(),
+            policy,
+            idle_input: None,
+            router: None,
+            message_len: 1,
+            current: 0,
+            running: 0,
+            quiet: 0,
+        }
+    }
+
+    // Adds a machine and returns its index.
+    pub fn add(&mut self, interpreter: Intcode) -> usize {
+        self.machines.push(Machine {
+            interpreter,
+            inputs: VecDeque::new(),
+            outputs: Vec::new(),
+            link: None,
+            waiting: false,
+            terminated: false,
+        });
+        self.running += 1;
+        self.machines.len() - 1
+    }
+
+    // Delivers the outputs of machine from to machine to. Used unless a router is set.
+    pub fn link(&mut self, from: usize, to: usize) {
+        self.machines[from].link = Some(to);
+    }
+
+    // Groups outputs into messages of message_len values and routes them with router, which is
+    // passed the index of the sending machine and the message.
+    pub fn set_router<F>(&mut self, message_len: usize, router: F)
+    where
+        F: FnMut(usize, &[isize]) -> Route + 'static,
+    {
+        self.message_len = message_len;
+        self.router = Some(Box::new(router));
+    }
+
+    // Value read by machines requesting input while their queue is empty, None to block them.
+    pub fn set_idle_input(&mut self, value: Option<isize>) {
+        self.idle_input = value;
+    }
+
+    // Panics if there is no such machine.
+    pub fn send(&mut self, machine: usize, values: &[isize]) {
+        self.machines[machine].inputs.extend(values);
+    }
+
+    // Values queued for the machine. Values sent to a machine that has terminated stay here.
+    pub fn inputs(&self, machine: usize) -> &VecDeque<isize> {
+        &self.machines[machine].inputs
+    }
+
+    pub fn interpreter(&self, machine: usize) -> &Intcode {
+        &self.machines[machine].interpreter
+    }
+
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+
+    fn route(&mut self, from: usize, message: Vec<isize>) -> Option<Event> {
+        let route = match (&mut self.router, self.machines[from].link) {
+            (Some(router), _) => router(from, &message),
+            (None, Some(to)) => Route::To(to, message.clone()),
+            (None, None) => Route::External,
+        };
+
+        match route {
+            Route::To(to, values) if to < self.machines.len() => {
+                self.send(to, &values);
+                None
+            }
+            Route::To(to, values) => Some(Event::Undeliverable(from, to, values)),
+            Route::External => Some(Event::Output(from, message)),
+        }
+    }
+
+    fn step(&mut self, index: usize) -> Result<Step, MachineError> {
+        let idle_input = self.idle_input;
+        let machine = &mut self.machines[index];
+
+        // Running up to the first input request is progress too.
+        let mut active = !machine.waiting;
+        let mut input = 0;
+        if machine.waiting {
+            // A replayed input takes the place of the queued ones.
+            if let Some(value) = machine.interpreter.recorded_input() {
+                input = value;
+                active = true;
+            } else {
+                match (machine.inputs.pop_front(), idle_input) {
+                    (Some(value), _) => {
+                        input = value;
+                        active = true;
+                    }
+                    (None, Some(value)) => input = value,
+                    (None, None) => return Ok(Step::Quiet),
+                }
+            }
+        }
+
+        let state = machine
+            .interpreter
+            .try_step(input)
+            .map_err(|error| MachineError {
+                machine: index,
+                error,
+            })?;
+        machine.waiting = state == State::Input;
+
+        match state {
+            State::Input => (),
+            State::Output(o) => {
+                machine.outputs.push(o);
+                if machine.outputs.len() == self.message_len {
+                    let message = std::mem::take(&mut machine.outputs);
+                    if let Some(event) = self.route(index, message) {
+                        return Ok(Step::Event(event));
+                    }
+                }
+                active = true;
+            }
+            State::Terminated => {
+                machine.terminated = true;
+                self.running -= 1;
+                return Ok(Step::Event(Event::Terminated(index)));
+            }
+        }
+
+        Ok(if active { Step::Active } else { Step::Quiet })
+    }
+
+    // Runs the machines until the next event.
+    pub fn run(&mut self) -> Result<Event, MachineError> {
+        loop {
+            if self.running == 0 {
+                return Ok(Event::Halted);
+            }
+            if self.quiet >= self.running {
+                self.quiet = 0;
+                return Ok(Event::Idle);
+            }
+
+            let index = self.current;
+            if self.machines[index].terminated {
+                self.current = (index + 1) % self.machines.len();
+                continue;
+            }
+
+            let step = self.step(index)?;
+            let turn_over = match step {
+                Step::Quiet => true,
+                Step::Active | Step::Event(Event::Output(..)) => self.policy == Policy::RoundRobin,
+                Step::Event(_) => true,
+            };
+            if turn_over {
+                self.current = (index + 1) % self.machines.len();
+            }
+
+            match step {
+                Step::Quiet => self.quiet += 1,
+                Step::Active => self.quiet = 0,
+                Step::Event(event) => {
+                    self.quiet = 0;
+                    return Ok(event);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,311 @@
+// Snapshots store the complete state of an Intcode machine in a line based text format:
+//
+//   intcode-snapshot 1
+//   iptr <instruction pointer>
+//   base <relative base>
+//   input_requested <0 or 1>
+//   limit <memory limit>
+//   high_water <high-water mark of the memory>
+//   arithmetic <wrapping or checked>
+//   retired <number of retired instructions>
+//   halted <0 or 1>
+//   segment <start address> <comma separated cells>
+//   ...
+//   end
+//
+// The first line holds the format version. There may be any number of segment lines, cells
+// that are not covered by a segment are zero. Snapshots without an arithmetic line restore a
+// machine with wrapping arithmetic, snapshots without a retired line one that has not retired
+// any instructions, and snapshots without a halted line one that has not halted.
+
+use crate::{Arithmetic, Intcode, Memory};
+use std::fmt;
+use std::io::{BufRead, Write};
+
+#[test]
+fn test_resume() {
+    use crate::State;
+
+    // Outputs the running sum of its inputs, the sum is kept in a relative variable.
+    let program = crate::asm::assemble(
+        "
+        .var sum, 0
+        .var value, 1
+                ARB #1000
+        loop:   IN [value]
+                ADD [sum], [value], [sum]
+                OUT [sum]
+                JNZ [value], #loop
+                HLT
+        ",
+    )
+    .unwrap();
+    let inputs = [3, 5, 7, 11, 13, 0];
+
+    let mut io = crate::BufIo::new(&inputs);
+    crate::evaluate_io(program.clone(), &mut io);
+
+    let mut interpreter = Intcode::new(program);
+    interpreter.set_arithmetic(Arithmetic::Checked);
+    let mut outputs = Vec::new();
+    let mut next = inputs.iter();
+    let mut input = 0;
+    while outputs.len() < 3 {
+        match interpreter.step(input) {
+            State::Input => input = *next.next().unwrap(),
+            State::Output(o) => outputs.push(o),
+            State::Terminated => panic!("Program terminated early"),
+        }
+    }
+
+    let mut buffer = Vec::new();
+    write_snapshot(&interpreter, &mut buffer).unwrap();
+    let mut restored = read_snapshot(&mut &buffer[..]).unwrap();
+
+    assert_eq!(interpreter.iptr(), restored.iptr());
+    assert_eq!(interpreter.relative_base(), restored.relative_base());
+    assert_eq!(Arithmetic::Checked, restored.arithmetic());
+    assert_eq!(
+        interpreter.instructions_retired(),
+        restored.instructions_retired()
+    );
+    assert_eq!(
+        interpreter.memory().high_water_mark(),
+        restored.memory().high_water_mark()
+    );
+
+    loop {
+        match restored.step(input) {
+            State::Input => input = *next.next().unwrap(),
+            State::Output(o) => outputs.push(o),
+            State::Terminated => break,
+        }
+    }
+
+    assert_eq!(io.output(), &outputs);
+
+    // A halted machine stays halted, it does not execute its HLT a second time.
+    let mut buffer = Vec::new();
+    write_snapshot(&restored, &mut buffer).unwrap();
+    let mut halted = read_snapshot(&mut &buffer[..]).unwrap();
+    assert_eq!(State::Terminated, halted.step(0));
+    assert_eq!(
+        restored.instructions_retired(),
+        halted.instructions_retired()
+    );
+
+    let snapshot = "intcode-snapshot 1\niptr 2\nbase 0\ninput_requested 0\nlimit 100\n\
+                    high_water 3\nsegment 0 99,0,99\nend\n";
+    let restored = read_snapshot(&mut snapshot.as_bytes()).unwrap();
+    assert_eq!((2, 0), (restored.iptr(), restored.instructions_retired()));
+}
+
+#[test]
+fn test_invalid_snapshot() {
+    let snapshot = "intcode-snapshot 2\niptr 0\n";
+    match read_snapshot(&mut snapshot.as_bytes()) {
+        Err(SnapshotError::Format(1, _)) => (),
+        _ => panic!("Expected unsupported version"),
+    }
+
+    let snapshot = "intcode-snapshot 1\niptr 0\nbase 0\nsegment 4 1,x\nend\n";
+    match read_snapshot(&mut snapshot.as_bytes()) {
+        Err(SnapshotError::Format(4, _)) => (),
+        _ => panic!("Expected invalid segment"),
+    }
+
+    let snapshot = "intcode-snapshot 1\niptr 0\n";
+    match read_snapshot(&mut snapshot.as_bytes()) {
+        Err(SnapshotError::Format(3, _)) => (),
+        _ => panic!("Expected truncated snapshot"),
+    }
+
+    let snapshot = format!(
+        "intcode-snapshot 1\niptr 0\nbase 0\ninput_requested 0\nlimit 100\nhigh_water 0\n\
+         segment {} 1,2\nend\n",
+        usize::MAX
+    );
+    match read_snapshot(&mut snapshot.as_bytes()) {
+        Err(SnapshotError::Format(7, _)) => (),
+        _ => panic!("Expected segment beyond the address space"),
+    }
+}
+
+pub const SNAPSHOT_VERSION: usize = 1;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    Format(usize, String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "Error while accessing snapshot: {}", e),
+            SnapshotError::Format(line, e) => write!(f, "Invalid snapshot in line {}: {}", line, e),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(e: std::io::Error) -> SnapshotError {
+        SnapshotError::Io(e)
+    }
+}
+
+pub fn write_snapshot(interpreter: &Intcode, writer: &mut dyn Write) -> std::io::Result<()> {
+    writeln!(writer, "intcode-snapshot {}", SNAPSHOT_VERSION)?;
+    writeln!(writer, "iptr {}", interpreter.iptr)?;
+    writeln!(writer, "base {}", interpreter.base)?;
+    writeln!(
+        writer,
+        "input_requested {}",
+        interpreter.input_requested as u8
+    )?;
+    writeln!(writer, "limit {}", interpreter.memory.limit())?;
+    writeln!(
+        writer,
+        "high_water {}",
+        interpreter.memory.high_water_mark()
+    )?;
+    let arithmetic = match interpreter.arithmetic {
+        Arithmetic::Wrapping => "wrapping",
+        Arithmetic::Checked => "checked",
+    };
+    writeln!(writer, "arithmetic {}", arithmetic)?;
+    writeln!(writer, "retired {}", interpreter.retired)?;
+    writeln!(writer, "halted {}", interpreter.halted as u8)?;
+
+    for (address, cells) in interpreter.memory.segments() {
+        let cells = cells
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        writeln!(writer, "segment {} {}", address, cells)?;
+    }
+
+    writeln!(writer, "end")
+}
+
+fn parse<T: std::str::FromStr>(line: usize, value: &str) -> Result<T, SnapshotError> {
+    value
+        .parse()
+        .map_err(|_| SnapshotError::Format(line, format!("Invalid number '{}'", value)))
+}
+
+pub fn read_snapshot(reader: &mut dyn BufRead) -> Result<Intcode, SnapshotError> {
+    let mut fields = Vec::new();
+    let mut segments = Vec::new();
+
+    let mut lines = reader.lines();
+    let mut number = 0;
+    loop {
+        number += 1;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Err(SnapshotError::Format(number, "Unexpected end".to_string())),
+        };
+
+        let mut words = line.split_whitespace();
+        let key = words.next().unwrap_or("");
+        let value = words.next().unwrap_or("");
+
+        match (number, key) {
+            (1, "intcode-snapshot") => {
+                if parse::<usize>(number, value)? != SNAPSHOT_VERSION {
+                    let message = format!("Unsupported version {}", value);
+                    return Err(SnapshotError::Format(number, message));
+                }
+            }
+            (1, _) => {
+                let message = "Missing snapshot header".to_string();
+                return Err(SnapshotError::Format(number, message));
+            }
+            (_, "segment") => {
+                let start: usize = parse(number, value)?;
+                let cells = words
+                    .next()
+                    .unwrap_or("")
+                    .split(',')
+                    .map(|c| parse(number, c))
+                    .collect::<Result<Vec<isize>, _>>()?;
+                segments.push((number, start, cells));
+            }
+            (_, "end") => break,
+            (_, _) => fields.push((number, key.to_string(), value.to_string())),
+        }
+    }
+
+    let field = |name: &str| -> Result<&(usize, String, String), SnapshotError> {
+        fields
+            .iter()
+            .find(|(_, k, _)| k == name)
+            .ok_or_else(|| SnapshotError::Format(number, format!("Missing field {}", name)))
+    };
+
+    let (line, _, iptr) = field("iptr")?;
+    let iptr = parse(*line, iptr)?;
+    let (line, _, base) = field("base")?;
+    let base = parse(*line, base)?;
+    let (line, _, input_requested) = field("input_requested")?;
+    let input_requested = parse::<u8>(*line, input_requested)? != 0;
+    let (line, _, limit) = field("limit")?;
+    let limit = parse(*line, limit)?;
+    let (line, _, high_water) = field("high_water")?;
+    let high_water = parse(*line, high_water)?;
+    let arithmetic = match fields.iter().find(|(_, k, _)| k == "arithmetic") {
+        None => Arithmetic::Wrapping,
+        Some((_, _, v)) if v == "wrapping" => Arithmetic::Wrapping,
+        Some((_, _, v)) if v == "checked" => Arithmetic::Checked,
+        Some((line, _, v)) => {
+            let message = format!("Invalid arithmetic '{}'", v);
+            return Err(SnapshotError::Format(*line, message));
+        }
+    };
+    let retired = match fields.iter().find(|(_, k, _)| k == "retired") {
+        Some((line, _, retired)) => parse(*line, retired)?,
+        None => 0,
+    };
+    let halted = match fields.iter().find(|(_, k, _)| k == "halted") {
+        Some((line, _, halted)) => parse::<u8>(*line, halted)? != 0,
+        None => false,
+    };
+
+    let mut memory = Memory::new(Vec::new(), limit);
+    for (line, start, cells) in segments {
+        for (i, value) in cells.into_iter().enumerate() {
+            let address = start.checked_add(i).ok_or_else(|| {
+                SnapshotError::Format(line, "Segment beyond the address space".to_string())
+            })?;
+            memory
+                .set(address, value)
+                .map_err(|e| SnapshotError::Format(line, e.to_string()))?;
+        }
+    }
+    memory.set_high_water_mark(high_water);
+
+    let mut interpreter = Intcode::from_memory(memory, arithmetic);
+    interpreter.iptr = iptr;
+    interpreter.base = base;
+    interpreter.input_requested = input_requested;
+    interpreter.retired = retired;
+    interpreter.halted = halted;
+    Ok(interpreter)
+}
+
+impl Intcode {
+    pub fn save_snapshot(&self, path: &str) -> Result<(), SnapshotError> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        write_snapshot(self, &mut file)?;
+        Ok(file.flush()?)
+    }
+
+    pub fn load_snapshot(path: &str) -> Result<Intcode, SnapshotError> {
+        let file = std::fs::File::open(path)?;
+        read_snapshot(&mut std::io::BufReader::new(file))
+    }
+}
@@ -0,0 +1,950 @@
+// Symbolic execution of Intcode programs. Memory cells and inputs may hold linear expressions over
+// integer variables instead of numbers. Comparisons and jumps on such expressions fork the
+// execution, each path collects the constraints under which it is taken. A small solver finds
+// values of the variables satisfying the constraints of a path.
+//
+// Values the engine cannot express, like the product of two variables or a cell read through a
+// symbolic address, become unknown. Running into an unknown value where it matters (a jump
+// condition, the address of a write, the relative base) ends the path as unsupported. So does a
+// branch the solver cannot decide within its budget. The engine computes with exact integers, a
+// path on which a result may not fit a memory cell ends instead of wrapping around like the VM.
+
+use crate::disasm::Op;
+use crate::DEFAULT_MEMORY_LIMIT;
+use std::collections::BTreeMap;
+
+#[test]
+fn test_solve() {
+    let x = Linear::var(0);
+    let y = Linear::var(1);
+    let sum = x.add(&y.scale(3));
+
+    let constraints = [
+        Constraint::new(sum.sub(&Linear::constant(25)), Relation::Eq),
+        Constraint::new(x.sub(&Linear::constant(4)), Relation::Ne),
+    ];
+    let values = solve(&[(0, 10), (0, 10)], &constraints).values().unwrap();
+    assert!(constraints.iter().all(|c| c.holds(&values)));
+    assert_ne!(4, values[0]);
+
+    let infeasible = Constraint::new(x.scale(2).sub(&Linear::constant(7)), Relation::Eq);
+    assert_eq!(
+        Solution::Infeasible,
+        solve(&[(0, 100), (0, 0)], &[infeasible])
+    );
+
+    // 2x = 2y + 1, and x = 2y = 2z + 1 over all cell values.
+    let full = (isize::MIN as i128, isize::MAX as i128);
+    let parity = x.scale(2).sub(&y.scale(2)).sub(&Linear::constant(1));
+    let parity = Constraint::new(parity, Relation::Eq);
+    assert_eq!(Solution::Infeasible, solve(&[full, full], &[parity]));
+    let z = Linear::var(2);
+    let constraints = [
+        Constraint::new(x.sub(&y.scale(2)), Relation::Eq),
+        Constraint::new(x.sub(&z.scale(2)).sub(&Linear::constant(1)), Relation::Eq),
+    ];
+    assert_eq!(
+        Solution::Infeasible,
+        solve(&[full, full, full], &constraints)
+    );
+}
+
+#[test]
+fn test_patched_cells() {
+    // Cell 0 ends up as 100 * noun + verb + 7. The first instruction reads through the patched
+    // cells, its result is overwritten by the second one.
+    let program = vec![
+        1, 0, 0, 3, 1, 1, 2, 3, 2, 1, 21, 0, 1, 0, 2, 0, 1, 0, 22, 0, 99, 100, 7,
+    ];
+    let mut engine = Engine::new(&program);
+    let noun = engine.variable(0, 99);
+    let verb = engine.variable(0, 99);
+    engine.patch(1, noun.clone());
+    engine.patch(2, verb.clone());
+
+    let paths = engine.explore();
+    assert_eq!(1, paths.len());
+    assert_eq!(End::Halted, paths[0].end);
+    let expected = noun.scale(100).add(&verb).add(&Linear::constant(7));
+    assert_eq!(Value::Known(expected), paths[0].cell(0));
+    assert_eq!(Some(vec![12, 34]), engine.solve_cell(0, 1241));
+    assert_eq!(None, engine.solve_cell(0, 10_007));
+}
+
+#[test]
+fn test_input_paths() {
+    // Outputs 1 for inputs below 10, three times the input otherwise.
+    let program = crate::asm::assemble(
+        "
+                IN [x]
+                LT [x], #10, [c]
+                JZ [c], #large
+                OUT #1
+                HLT
+        large:  MUL [x], #3, [x]
+                OUT [x]
+                HLT
+        x:      .data 0
+        c:      .data 0
+        ",
+    )
+    .unwrap();
+    let mut engine = Engine::new(&program);
+    engine.set_input_range(-1000, 1000);
+
+    let paths = engine.explore();
+    assert_eq!(2, paths.len());
+
+    let goal = |path: &Path| match &path.outputs[0] {
+        Value::Known(o) => path
+            .solve(&[Constraint::new(o.sub(&Linear::constant(42)), Relation::Eq)])
+            .values(),
+        Value::Unknown => None,
+    };
+    let solutions: Vec<_> = paths.iter().filter_map(goal).collect();
+    assert_eq!(vec![vec![14]], solutions);
+}
+
+#[test]
+fn test_infeasible_paths() {
+    // Outputs 1 if twice the first input equals twice the second one plus one, which never
+    // holds. Then outputs 2 if x = 2y = 2z + 1, which never holds either.
+    let program = crate::asm::assemble(
+        "
+                IN [x]
+                IN [y]
+                IN [z]
+                MUL [x], #2, [a]
+                MUL [y], #2, [b]
+                ADD [b], #1, [b]
+                EQ [a], [b], [c]
+                JZ [c], #next
+                OUT #1
+        next:   MUL [y], #2, [a]
+                EQ [x], [a], [c]
+                JZ [c], #end
+                MUL [z], #2, [b]
+                ADD [b], #1, [b]
+                EQ [x], [b], [c]
+                JZ [c], #end
+                OUT #2
+        end:    HLT
+        x:      .data 0
+        y:      .data 0
+        z:      .data 0
+        a:      .data 0
+        b:      .data 0
+        c:      .data 0
+        ",
+    )
+    .unwrap();
+
+    let mut engine = Engine::new(&program);
+    engine.set_input_range(-1000, 1000);
+    let paths = engine.explore();
+    assert_eq!(2, paths.len());
+    assert!(paths.iter().all(|p| p.end == End::Halted));
+    assert!(paths.iter().all(|p| p.outputs.is_empty()));
+
+    // Over all cell values twice the first input may overflow.
+    let paths = Engine::new(&program).explore();
+    assert_eq!(1, paths.len());
+    assert_eq!(End::Overflow(6), paths[0].end);
+}
+
+// Number of rounds of bound propagation before the solver branches.
+const PROPAGATION_ROUNDS: usize = 64;
+
+// Number of branches the solver explores before it gives up.
+const SEARCH_NODES: usize = 10_000;
+
+// Largest constant or coefficient in constraints produced by eliminating a variable.
+const ELIMINATION_LIMIT: i128 = 1 << 100;
+
+// A linear expression over variables, identified by their index.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Linear {
+    pub constant: i128,
+    // Coefficients of the variables, none of them zero.
+    pub terms: BTreeMap<usize, i128>,
+}
+
+impl Linear {
+    pub fn constant(value: i128) -> Linear {
+        Linear {
+            constant: value,
+            terms: BTreeMap::new(),
+        }
+    }
+
+    pub fn var(index: usize) -> Linear {
+        let mut terms = BTreeMap::new();
+        terms.insert(index, 1);
+        Linear { constant: 0, terms }
+    }
+
+    pub fn as_constant(&self) -> Option<i128> {
+        if self.terms.is_empty() {
+            Some(self.constant)
+        } else {
+            None
+        }
+    }
+
+    pub fn add(&self, other: &Linear) -> Linear {
+        let mut result = self.clone();
+        result.constant = result.constant.saturating_add(other.constant);
+        for (&var, &coefficient) in &other.terms {
+            let c = result.terms.entry(var).or_insert(0);
+            *c = c.saturating_add(coefficient);
+            if *c == 0 {
+                result.terms.remove(&var);
+            }
+        }
+        result
+    }
+
+    pub fn scale(&self, factor: i128) -> Linear {
+        if factor == 0 {
+            return Linear::default();
+        }
+        Linear {
+            constant: self.constant.saturating_mul(factor),
+            terms: self
+                .terms
+                .iter()
+                .map(|(&v, &c)| (v, c.saturating_mul(factor)))
+                .collect(),
+        }
+    }
+
+    pub fn sub(&self, other: &Linear) -> Linear {
+        self.add(&other.scale(-1))
+    }
+
+    // Value of the expression for the given values of the variables.
+    pub fn evaluate(&self, values: &[i128]) -> Option<i128> {
+        self.terms.iter().try_fold(self.constant, |sum, (&v, &c)| {
+            sum.checked_add(c.checked_mul(*values.get(v)?)?)
+        })
+    }
+
+    // Whether the constant and all coefficients are within the range of a memory cell.
+    fn fits(&self) -> bool {
+        let range = (isize::MIN as i128)..=(isize::MAX as i128);
+        range.contains(&self.constant) && self.terms.values().all(|c| range.contains(c))
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Value {
+    Known(Linear),
+    Unknown,
+}
+
+impl Value {
+    fn constant(value: isize) -> Value {
+        Value::Known(Linear::constant(value as i128))
+    }
+
+    fn as_constant(&self) -> Option<i128> {
+        match self {
+            Value::Known(l) => l.as_constant(),
+            Value::Unknown => None,
+        }
+    }
+
+    // Unknown if the result does not fit a memory cell.
+    fn checked(expr: Linear) -> Value {
+        if expr.fits() {
+            Value::Known(expr)
+        } else {
+            Value::Unknown
+        }
+    }
+
+    pub fn add(&self, other: &Value) -> Value {
+        match (self, other) {
+            (Value::Known(a), Value::Known(b)) => Value::checked(a.add(b)),
+            _ => Value::Unknown,
+        }
+    }
+
+    // Unknown unless one of the factors is a constant.
+    pub fn mul(&self, other: &Value) -> Value {
+        match (self, other) {
+            (Value::Known(a), Value::Known(b)) => match (a.as_constant(), b.as_constant()) {
+                (Some(f), _) => Value::checked(b.scale(f)),
+                (_, Some(f)) => Value::checked(a.scale(f)),
+                _ => Value::Unknown,
+            },
+            _ => Value::Unknown,
+        }
+    }
+}
+
+// Relation of an expression to zero.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Relation {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+}
+
+impl Relation {
+    pub fn negate(self) -> Relation {
+        match self {
+            Relation::Eq => Relation::Ne,
+            Relation::Ne => Relation::Eq,
+            Relation::Lt => Relation::Ge,
+            Relation::Ge => Relation::Lt,
+        }
+    }
+}
+
+// The constraint `expr <relation> 0`.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Constraint {
+    pub expr: Linear,
+    pub relation: Relation,
+}
+
+impl Constraint {
+    pub fn new(expr: Linear, relation: Relation) -> Constraint {
+        Constraint { expr, relation }
+    }
+
+    pub fn negate(&self) -> Constraint {
+        Constraint::new(self.expr.clone(), self.relation.negate())
+    }
+
+    pub fn holds(&self, values: &[i128]) -> bool {
+        match self.expr.evaluate(values) {
+            Some(v) => match self.relation {
+                Relation::Eq => v == 0,
+                Relation::Ne => v != 0,
+                Relation::Lt => v < 0,
+                Relation::Ge => v >= 0,
+            },
+            None => false,
+        }
+    }
+
+    // Expressions that have to be at most zero, not covering Ne.
+    fn upper_bounds(&self) -> Vec<Linear> {
+        match self.relation {
+            Relation::Eq => vec![self.expr.clone(), self.expr.scale(-1)],
+            Relation::Ne => vec![],
+            Relation::Lt => vec![self.expr.add(&Linear::constant(1))],
+            Relation::Ge => vec![self.expr.scale(-1)],
+        }
+    }
+}
+
+fn floor_div(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn ceil_div(a: i128, b: i128) -> i128 {
+    floor_div(a.saturating_neg(), b).saturating_neg()
+}
+
+// Narrows the domains with `expr <= 0`. Returns None if the constraint cannot hold, otherwise
+// whether a domain changed.
+fn tighten(domains: &mut [(i128, i128)], expr: &Linear) -> Option<bool> {
+    // Smallest value of c * x for the variable v.
+    let minimum = |domains: &[(i128, i128)], v: usize, c: i128| {
+        let (lo, hi) = domains[v];
+        c.saturating_mul(if c > 0 { lo } else { hi })
+    };
+    let total = expr.terms.iter().fold(expr.constant, |sum, (&v, &c)| {
+        sum.saturating_add(minimum(domains, v, c))
+    });
+
+    if total > 0 {
+        return None;
+    }
+    if total == i128::MIN {
+        return Some(false);
+    }
+
+    let mut changed = false;
+    for (&v, &c) in &expr.terms {
+        // c * x <= bound
+        let bound = total
+            .saturating_sub(minimum(domains, v, c))
+            .saturating_neg();
+        let (lo, hi) = &mut domains[v];
+        if c > 0 {
+            let limit = floor_div(bound, c);
+            if limit < *hi {
+                *hi = limit;
+                changed = true;
+            }
+        } else {
+            let limit = ceil_div(bound, c);
+            if limit > *lo {
+                *lo = limit;
+                changed = true;
+            }
+        }
+        if lo > hi {
+            return None;
+        }
+    }
+    Some(changed)
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// Whether an equality can hold for integers, that is the gcd of its coefficients divides the
+// constant.
+fn divisible(constraint: &Constraint) -> bool {
+    let divisor = constraint.expr.terms.values().fold(0, |g, &c| gcd(g, c));
+    constraint.relation != Relation::Eq || divisor == 0 || constraint.expr.constant % divisor == 0
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Solution {
+    Found(Vec<i128>),
+    Infeasible,
+    // The solver gave up before it found values or ruled them out.
+    Unknown,
+}
+
+impl Solution {
+    pub fn values(self) -> Option<Vec<i128>> {
+        match self {
+            Solution::Found(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+// Searches the domains, visiting at most nodes branches.
+fn search(
+    mut domains: Vec<(i128, i128)>,
+    constraints: &[Constraint],
+    nodes: &mut usize,
+) -> Solution {
+    if *nodes == 0 {
+        return Solution::Unknown;
+    }
+    *nodes -= 1;
+
+    let bounds: Vec<_> = constraints.iter().flat_map(|c| c.upper_bounds()).collect();
+    for _ in 0..PROPAGATION_ROUNDS {
+        let mut changed = false;
+        for expr in &bounds {
+            match tighten(&mut domains, expr) {
+                Some(c) => changed |= c,
+                None => return Solution::Infeasible,
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    // Branch on the constrained variable with the smallest domain.
+    let open = constraints
+        .iter()
+        .flat_map(|c| c.expr.terms.keys())
+        .copied()
+        .filter(|&v| domains[v].0 < domains[v].1)
+        .min_by_key(|&v| domains[v].1 - domains[v].0);
+
+    match open {
+        None => {
+            let values: Vec<_> = domains.iter().map(|d| d.0).collect();
+            if constraints.iter().all(|c| c.holds(&values)) {
+                Solution::Found(values)
+            } else {
+                Solution::Infeasible
+            }
+        }
+        Some(v) => {
+            let (lo, hi) = domains[v];
+            let mid = lo + (hi - lo) / 2;
+            let mut lower = domains.clone();
+            lower[v] = (lo, mid);
+            domains[v] = (mid + 1, hi);
+            match search(lower, constraints, nodes) {
+                Solution::Infeasible => search(domains, constraints, nodes),
+                solution => solution,
+            }
+        }
+    }
+}
+
+// The expression with the variable v replaced by value.
+fn substitute(expr: &Linear, v: usize, value: &Linear) -> Linear {
+    match expr.terms.get(&v) {
+        Some(&c) => {
+            let mut rest = expr.clone();
+            rest.terms.remove(&v);
+            rest.add(&value.scale(c))
+        }
+        None => expr.clone(),
+    }
+}
+
+// Solves equalities for variables with a coefficient of 1 or -1 and substitutes them into the
+// other constraints, which lets the divisibility check see through chains like x = 2y = 2z + 1.
+// The domain of an eliminated variable becomes a pair of constraints. Returns the eliminated
+// variables with their values in terms of the remaining ones, in the order of elimination.
+fn eliminate(domains: &[(i128, i128)], constraints: &mut Vec<Constraint>) -> Vec<(usize, Linear)> {
+    let mut substitutions = Vec::new();
+    loop {
+        let pivot = constraints
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.relation == Relation::Eq)
+            .find_map(|(i, c)| {
+                let (&v, &c) = c.expr.terms.iter().find(|(_, &c)| c == 1 || c == -1)?;
+                Some((i, v, c))
+            });
+        let (i, v, c) = match pivot {
+            Some(pivot) => pivot,
+            None => break,
+        };
+
+        // c * v + rest = 0, so v = -c * rest.
+        let mut value = constraints[i].expr.clone();
+        value.terms.remove(&v);
+        let value = value.scale(-c);
+        let (lo, hi) = domains[v];
+        let mut reduced: Vec<_> = constraints
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .map(|(_, c)| Constraint::new(substitute(&c.expr, v, &value), c.relation))
+            .collect();
+        reduced.push(Constraint::new(
+            value.sub(&Linear::constant(lo)),
+            Relation::Ge,
+        ));
+        reduced.push(Constraint::new(
+            Linear::constant(hi).sub(&value),
+            Relation::Ge,
+        ));
+        // Keep the numbers far from the saturating arithmetic of Linear.
+        let small = |x: &i128| x.abs() <= ELIMINATION_LIMIT;
+        let small = |c: &Constraint| small(&c.expr.constant) && c.expr.terms.values().all(small);
+        if !reduced.iter().all(small) {
+            break;
+        }
+        *constraints = reduced;
+        substitutions.push((v, value));
+    }
+    substitutions
+}
+
+// Finds values for the variables within the inclusive domains that satisfy all constraints.
+pub fn solve(domains: &[(i128, i128)], constraints: &[Constraint]) -> Solution {
+    if domains.iter().any(|(lo, hi)| lo > hi) || !constraints.iter().all(divisible) {
+        return Solution::Infeasible;
+    }
+    let mut constraints = constraints.to_vec();
+    let substitutions = eliminate(domains, &mut constraints);
+    if !constraints.iter().all(divisible) {
+        return Solution::Infeasible;
+    }
+
+    let mut nodes = SEARCH_NODES;
+    match search(domains.to_vec(), &constraints, &mut nodes) {
+        Solution::Found(mut values) => {
+            for (v, value) in substitutions.iter().rev() {
+                match value.evaluate(&values) {
+                    Some(x) => values[*v] = x,
+                    None => return Solution::Unknown,
+                }
+            }
+            Solution::Found(values)
+        }
+        solution => solution,
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum End {
+    Halted,
+    // The instruction at this address depends on an unknown value.
+    Unsupported(usize),
+    // The instruction at this address faults.
+    Fault(usize),
+    StepLimit,
+    // The path was not followed because too many paths were explored.
+    PathLimit,
+    // The solver could not decide whether the branch at this address can be taken.
+    SolverLimit(usize),
+    // The result of the instruction at this address may not fit a memory cell, where the VM
+    // would wrap it around.
+    Overflow(usize),
+}
+
+// A path through the program and the constraints under which it is taken.
+#[derive(Clone, Debug)]
+pub struct Path {
+    pub end: End,
+    pub constraints: Vec<Constraint>,
+    pub outputs: Vec<Value>,
+    // Variables holding the values read by input instructions, in order.
+    pub inputs: Vec<usize>,
+    iptr: usize,
+    base: isize,
+    steps: usize,
+    // Cells that differ from zero or were written, kept sparse as writes may go anywhere.
+    memory: BTreeMap<usize, Value>,
+    domains: Vec<(i128, i128)>,
+    input_range: (i128, i128),
+}
+
+impl Path {
+    // The content of the memory cell at the end of the path.
+    pub fn cell(&self, address: usize) -> Value {
+        self.memory
+            .get(&address)
+            .cloned()
+            .unwrap_or_else(|| Value::constant(0))
+    }
+
+    // Values for all variables, including the inputs, so that the path is taken and the
+    // additional constraints hold.
+    pub fn solve(&self, additional: &[Constraint]) -> Solution {
+        let mut constraints = self.constraints.clone();
+        constraints.extend_from_slice(additional);
+        solve(&self.domains, &constraints)
+    }
+
+    // The path continuing under the additional constraint, None if it cannot be taken.
+    fn assume(&self, constraint: Constraint) -> Result<Option<Path>, End> {
+        let mut path = self.clone();
+        path.constraints.push(constraint);
+        match path.solve(&[]) {
+            Solution::Found(_) => Ok(Some(path)),
+            Solution::Infeasible => Ok(None),
+            Solution::Unknown => Err(End::SolverLimit(self.iptr)),
+        }
+    }
+
+    fn write(&mut self, address: usize, value: Value) {
+        self.memory.insert(address, value);
+    }
+
+    // Ends the path if the value may lie outside the range of a memory cell for some values of
+    // the variables.
+    fn check_overflow(&self, value: &Value) -> Result<(), End> {
+        let expr = match value {
+            Value::Known(expr) => expr,
+            Value::Unknown => return Ok(()),
+        };
+        let (lo, hi) =
+            expr.terms
+                .iter()
+                .fold((expr.constant, expr.constant), |(lo, hi), (&v, &c)| {
+                    let (a, b) = self.domains[v];
+                    let (a, b) = (c.saturating_mul(a), c.saturating_mul(b));
+                    (
+                        lo.saturating_add(std::cmp::min(a, b)),
+                        hi.saturating_add(std::cmp::max(a, b)),
+                    )
+                });
+        if lo >= isize::MIN as i128 && hi <= isize::MAX as i128 {
+            return Ok(());
+        }
+
+        // The bounds ignore the constraints of the path, ask the solver.
+        let below = expr.sub(&Linear::constant(isize::MIN as i128));
+        let above = expr.sub(&Linear::constant(isize::MAX as i128));
+        let outside = [
+            Constraint::new(below, Relation::Lt),
+            Constraint::new(above.scale(-1), Relation::Lt),
+        ];
+        for constraint in &outside {
+            if self.assume(constraint.clone())?.is_some() {
+                return Err(End::Overflow(self.iptr));
+            }
+        }
+        Ok(())
+    }
+
+    // Address a parameter refers to, None if it is symbolic.
+    fn address(&self, mode: isize, raw: &Value) -> Result<Option<usize>, End> {
+        let offset = match mode {
+            0 => 0,
+            2 => self.base as i128,
+            _ => return Err(End::Fault(self.iptr)),
+        };
+        match raw.as_constant() {
+            Some(a) if a + offset < 0 || a + offset >= DEFAULT_MEMORY_LIMIT as i128 => {
+                Err(End::Fault(self.iptr))
+            }
+            Some(a) => Ok(Some((a + offset) as usize)),
+            None => Ok(None),
+        }
+    }
+
+    fn read(&self, mode: isize, raw: &Value) -> Result<Value, End> {
+        if mode == 1 {
+            return Ok(raw.clone());
+        }
+        Ok(match self.address(mode, raw)? {
+            Some(a) => self.cell(a),
+            None => Value::Unknown,
+        })
+    }
+
+    fn target(&self, mode: isize, raw: &Value) -> Result<usize, End> {
+        self.address(mode, raw)?.ok_or(End::Unsupported(self.iptr))
+    }
+
+    // Executes a single instruction. Alternative paths forked off are added to forks.
+    fn step(&mut self, forks: &mut Vec<Path>) -> Result<(), End> {
+        self.steps += 1;
+        let opcode = self
+            .cell(self.iptr)
+            .as_constant()
+            .ok_or(End::Unsupported(self.iptr))?;
+        let op = Op::from_code((opcode % 100) as isize).ok_or(End::Fault(self.iptr))?;
+        let raw: Vec<_> = (1..=op.arity()).map(|k| self.cell(self.iptr + k)).collect();
+        let mode = |k: usize| ((opcode / [100, 1000, 10000][k]) % 10) as isize;
+        let next = self.iptr + 1 + op.arity();
+
+        match op {
+            Op::Add | Op::Mul | Op::Lt | Op::Eq => {
+                let a = self.read(mode(0), &raw[0])?;
+                let b = self.read(mode(1), &raw[1])?;
+                let t = self.target(mode(2), &raw[2])?;
+                let result = match (op, &a, &b) {
+                    (Op::Add, ..) | (Op::Mul, ..) => {
+                        let result = if op == Op::Add { a.add(&b) } else { a.mul(&b) };
+                        self.check_overflow(&result)?;
+                        result
+                    }
+                    (_, Value::Known(a), Value::Known(b)) => {
+                        let relation = if op == Op::Lt {
+                            Relation::Lt
+                        } else {
+                            Relation::Eq
+                        };
+                        let constraint = Constraint::new(a.sub(b), relation);
+                        match constraint.expr.as_constant() {
+                            Some(_) => Value::constant(constraint.holds(&[]) as isize),
+                            None => {
+                                let mut other = self.assume(constraint.negate())?;
+                                if let Some(other) = &mut other {
+                                    other.write(t, Value::constant(0));
+                                    other.iptr = next;
+                                }
+                                match self.assume(constraint)? {
+                                    Some(path) => {
+                                        forks.extend(other);
+                                        *self = path;
+                                        Value::constant(1)
+                                    }
+                                    None => {
+                                        *self = other.ok_or(End::Unsupported(self.iptr))?;
+                                        return Ok(());
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    _ => Value::Unknown,
+                };
+                self.write(t, result);
+            }
+            Op::In => {
+                let t = self.target(mode(0), &raw[0])?;
+                let var = self.domains.len();
+                self.domains.push(self.input_range);
+                self.inputs.push(var);
+                self.write(t, Value::Known(Linear::var(var)));
+            }
+            Op::Out => {
+                let value = self.read(mode(0), &raw[0])?;
+                self.outputs.push(value);
+            }
+            Op::Arb => {
+                let offset = self.read(mode(0), &raw[0])?.as_constant();
+                let base = offset.map(|o| o + self.base as i128);
+                self.base = match base {
+                    Some(b) if b >= isize::MIN as i128 && b <= isize::MAX as i128 => b as isize,
+                    Some(_) => return Err(End::Fault(self.iptr)),
+                    None => return Err(End::Unsupported(self.iptr)),
+                };
+            }
+            Op::Jnz | Op::Jz => {
+                let cond = self.read(mode(0), &raw[0])?;
+                let target = self.read(mode(1), &raw[1])?;
+                let relation = if op == Op::Jnz {
+                    Relation::Ne
+                } else {
+                    Relation::Eq
+                };
+                let taken = match cond {
+                    Value::Known(c) => Constraint::new(c, relation),
+                    Value::Unknown => return Err(End::Unsupported(self.iptr)),
+                };
+
+                let jump = |path: &mut Path| match target.as_constant() {
+                    Some(t) if t >= 0 => {
+                        path.iptr = t as usize;
+                        Ok(())
+                    }
+                    Some(_) => Err(End::Fault(path.iptr)),
+                    None => Err(End::Unsupported(path.iptr)),
+                };
+
+                if taken.expr.as_constant().is_some() {
+                    if taken.holds(&[]) {
+                        return jump(self);
+                    }
+                } else {
+                    let mut other = self.assume(taken.negate())?;
+                    if let Some(other) = &mut other {
+                        other.iptr = next;
+                    }
+                    match self.assume(taken)? {
+                        Some(path) => {
+                            forks.extend(other);
+                            *self = path;
+                            return jump(self);
+                        }
+                        None => {
+                            *self = other.ok_or(End::Unsupported(self.iptr))?;
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            Op::Hlt => return Err(End::Halted),
+        }
+
+        self.iptr = next;
+        Ok(())
+    }
+}
+
+// Sets up and explores the paths of a program.
+pub struct Engine {
+    program: Vec<isize>,
+    domains: Vec<(i128, i128)>,
+    patches: Vec<(usize, Linear)>,
+    input_range: (i128, i128),
+    step_limit: usize,
+    path_limit: usize,
+}
+
+impl Engine {
+    pub fn new(program: &[isize]) -> Engine {
+        Engine {
+            program: program.to_vec(),
+            domains: Vec::new(),
+            patches: Vec::new(),
+            input_range: (isize::MIN as i128, isize::MAX as i128),
+            step_limit: 1_000_000,
+            path_limit: 1000,
+        }
+    }
+
+    // A new variable with values in min..=max.
+    pub fn variable(&mut self, min: isize, max: isize) -> Linear {
+        self.domains.push((min as i128, max as i128));
+        Linear::var(self.domains.len() - 1)
+    }
+
+    // Replaces the memory cell at address with the expression before running.
+    pub fn patch(&mut self, address: usize, value: Linear) {
+        self.patches.push((address, value));
+    }
+
+    // Range of the values input instructions may read, all cell values by default.
+    pub fn set_input_range(&mut self, min: isize, max: isize) {
+        self.input_range = (min as i128, max as i128);
+    }
+
+    // Number of instructions executed on a single path before it is abandoned.
+    pub fn set_step_limit(&mut self, limit: usize) {
+        self.step_limit = limit;
+    }
+
+    // Number of paths explored before the remaining ones are abandoned.
+    pub fn set_path_limit(&mut self, limit: usize) {
+        self.path_limit = limit;
+    }
+
+    pub fn explore(&self) -> Vec<Path> {
+        let mut start = Path {
+            end: End::Halted,
+            constraints: Vec::new(),
+            outputs: Vec::new(),
+            inputs: Vec::new(),
+            iptr: 0,
+            base: 0,
+            steps: 0,
+            memory: self
+                .program
+                .iter()
+                .enumerate()
+                .filter(|(_, &c)| c != 0)
+                .map(|(a, &c)| (a, Value::constant(c)))
+                .collect(),
+            domains: self.domains.clone(),
+            input_range: self.input_range,
+        };
+        for (address, value) in &self.patches {
+            start.write(*address, Value::Known(value.clone()));
+        }
+
+        let mut paths = Vec::new();
+        let mut work = vec![start];
+        while let Some(mut path) = work.pop() {
+            if paths.len() >= self.path_limit {
+                path.end = End::PathLimit;
+                paths.push(path);
+                continue;
+            }
+
+            path.end = loop {
+                if path.steps >= self.step_limit {
+                    break End::StepLimit;
+                }
+                if let Err(end) = path.step(&mut work) {
+                    break end;
+                }
+            };
+            paths.push(path);
+        }
+        paths
+    }
+
+    // Values of the variables for which the program halts with value in the memory cell at
+    // address.
+    pub fn solve_cell(&self, address: usize, value: isize) -> Option<Vec<i128>> {
+        self.explore()
+            .iter()
+            .filter(|path| path.end == End::Halted)
+            .find_map(|path| match path.cell(address) {
+                Value::Known(expr) => {
+                    let goal = expr.sub(&Linear::constant(value as i128));
+                    path.solve(&[Constraint::new(goal, Relation::Eq)]).values()
+                }
+                Value::Unknown => None,
+            })
+            .map(|values| values[..self.domains.len()].to_vec())
+    }
+}
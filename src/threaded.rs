@@ -0,0 +1,156 @@
+use crate::{InputError, Intcode, IntcodeError, Io, State};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::JoinHandle;
+
+#[test]
+fn test_spawn() {
+    // Doubles every input until it reads a zero.
+    let program = crate::asm::assemble(
+        "
+        loop:   IN [value]
+                JZ [value], #end
+                MUL [value], #2, [value]
+                OUT [value]
+                JNZ #1, #loop
+        end:    HLT
+        value:  .data 0
+        ",
+    )
+    .unwrap();
+
+    let handle = spawn(Intcode::new(program));
+    for i in 1..4 {
+        handle.input.send(i).unwrap();
+    }
+    handle.input.send(0).unwrap();
+
+    let outputs: Vec<_> = handle.output.iter().collect();
+    assert_eq!(vec![2, 4, 6], outputs);
+    assert_eq!(Ok(Exit::Terminated), handle.thread.join().unwrap());
+}
+
+#[test]
+fn test_input_closed() {
+    let handle = spawn(Intcode::new(vec![3, 7, 4, 7, 1105, 1, 0, 0]));
+    handle.input.send(5).unwrap();
+    handle.input.send(6).unwrap();
+
+    let Handle {
+        input,
+        output,
+        thread,
+    } = handle;
+    drop(input);
+
+    assert_eq!(vec![5, 6], output.iter().collect::<Vec<_>>());
+    assert_eq!(Ok(Exit::InputClosed), thread.join().unwrap());
+}
+
+#[test]
+fn test_error() {
+    let handle = spawn(Intcode::new(vec![104, 42, 42]));
+    assert_eq!(Ok(42), handle.output.recv());
+    let result = handle.thread.join().unwrap();
+    assert_eq!(crate::ErrorKind::InvalidOpcode, result.unwrap_err().kind);
+}
+
+#[test]
+fn test_channel_io() {
+    // Outputs the sum of every two inputs.
+    let program = vec![3, 13, 3, 14, 1, 13, 14, 15, 4, 15, 1105, 1, 0, 0, 0, 0];
+    let (input, thread_input) = channel();
+    let (thread_output, output) = channel();
+
+    let thread = std::thread::spawn(move || {
+        let mut io = ChannelIo::new(thread_input, thread_output);
+        Intcode::new(program).run(&mut io)
+    });
+    for i in 1..5 {
+        input.send(i).unwrap();
+    }
+    drop(input);
+
+    assert_eq!(vec![3, 7], output.iter().collect::<Vec<_>>());
+    assert_eq!(Ok(State::Input), thread.join().unwrap());
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Exit {
+    Terminated,
+    // The program requested input, but all senders of the input channel are gone.
+    InputClosed,
+    // The program produced output, but the receiver of the output channel is gone.
+    OutputClosed,
+}
+
+// Io backed by channels, to run a machine with Intcode::run on another thread. Reading input
+// blocks until a value arrives, a closed input channel ends the input.
+pub struct ChannelIo {
+    input: Receiver<isize>,
+    output: Sender<isize>,
+}
+
+impl ChannelIo {
+    pub fn new(input: Receiver<isize>, output: Sender<isize>) -> ChannelIo {
+        ChannelIo { input, output }
+    }
+}
+
+impl Io for ChannelIo {
+    fn input(&mut self) -> Result<isize, InputError> {
+        self.input.recv().map_err(|_| InputError::End)
+    }
+
+    fn output(&mut self, o: isize) {
+        // Output nobody listens to is dropped.
+        let _ = self.output.send(o);
+    }
+}
+
+pub struct Handle {
+    pub input: Sender<isize>,
+    pub output: Receiver<isize>,
+    pub thread: JoinHandle<Result<Exit, IntcodeError>>,
+}
+
+// Runs the machine on its own thread, reading input from and writing output to the given
+// channels. Inputs replayed by the tap of the machine are not read from the channel. The thread
+// ends when the program halts or one of the channels is closed; it drops its ends of the
+// channels, so a receiver of the output sees the end of the stream.
+pub fn spawn_connected(
+    mut interpreter: Intcode,
+    input: Receiver<isize>,
+    output: Sender<isize>,
+) -> JoinHandle<Result<Exit, IntcodeError>> {
+    std::thread::spawn(move || {
+        let mut value = 0;
+        loop {
+            match interpreter.try_step(value)? {
+                State::Input => match interpreter.recorded_input() {
+                    Some(v) => value = v,
+                    None => match input.recv() {
+                        Ok(v) => value = v,
+                        Err(_) => return Ok(Exit::InputClosed),
+                    },
+                },
+                State::Output(o) => {
+                    if output.send(o).is_err() {
+                        return Ok(Exit::OutputClosed);
+                    }
+                }
+                State::Terminated => return Ok(Exit::Terminated),
+            }
+        }
+    })
+}
+
+pub fn spawn(interpreter: Intcode) -> Handle {
+    let (input, thread_input) = channel();
+    let (thread_output, output) = channel();
+
+    Handle {
+        input,
+        output,
+        thread: spawn_connected(interpreter, thread_input, thread_output),
+    }
+}
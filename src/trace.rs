@@ -0,0 +1,300 @@
+use crate::disasm::Op;
+use crate::extension::Param;
+use crate::{Cell, Intcode};
+use std::convert::TryFrom;
+use std::io::Write;
+
+#[test]
+fn test_trace() {
+    let mut interpreter = Intcode::new(vec![109, 3, 1201, -1, 4, 11, 3, 11, 4, 11, 99, 0]);
+    let mut records = Vec::new();
+    let mut io = crate::BufIo::new(&[7]);
+    interpreter.run_traced(&mut io, &mut records).unwrap();
+
+    assert_eq!(5, records.len());
+    assert_eq!((1, 2), (records[1].step, records[1].address));
+    assert_eq!(Some(Op::Add), records[1].op);
+    assert_eq!([2, 1, 0], records[1].modes);
+    assert_eq!(
+        vec![
+            Operand {
+                mode: 2,
+                raw: -1,
+                address: Some(2),
+                value: Some(1201)
+            },
+            Operand {
+                mode: 1,
+                raw: 4,
+                address: None,
+                value: Some(4)
+            },
+            Operand {
+                mode: 0,
+                raw: 11,
+                address: Some(11),
+                value: None
+            },
+        ],
+        records[1].operands
+    );
+    assert_eq!(Some((11, 1205)), records[1].write);
+    assert_eq!(3, records[1].base);
+    assert_eq!(Some((11, 7)), records[2].write);
+    assert_eq!(Some(7), records[3].operands[0].value);
+    assert_eq!(Some(Op::Hlt), records[4].op);
+    assert_eq!(5, interpreter.instructions_retired());
+}
+
+#[test]
+fn test_extension() {
+    use crate::extension::Random;
+    use std::sync::{Arc, Mutex};
+
+    // RND [4]; HLT
+    let mut interpreter = Intcode::new(vec![50, 4, 99, 0, 0]);
+    interpreter.register(50, Arc::new(Mutex::new(Random::new(1))));
+    let mut records = Vec::new();
+    interpreter
+        .run_traced(&mut crate::BufIo::new(&[]), &mut records)
+        .unwrap();
+
+    let random = Random::new(1).next_value();
+    assert_eq!(None, records[0].op);
+    assert_eq!(
+        vec![Operand {
+            mode: 0,
+            raw: 4,
+            address: Some(4),
+            value: None
+        }],
+        records[0].operands
+    );
+    assert_eq!(Some((4, random)), records[0].write);
+    assert_eq!(2, records[1].address);
+}
+
+#[test]
+fn test_truncated_instruction() {
+    // The last parameter of the ADD lies beyond the memory limit.
+    let interpreter = Intcode::with_memory_limit(vec![1101, 2, 3], 3);
+    let record = begin(&interpreter).unwrap();
+    assert_eq!(3, record.operands.len());
+    assert_eq!(Some(3), record.operands[1].value);
+    assert_eq!(
+        (None, None),
+        (record.operands[2].address, record.operands[2].value)
+    );
+}
+
+#[test]
+fn test_json() {
+    let mut interpreter = Intcode::new(vec![1101, 2, -3, 5, 99, 0]);
+    let mut tracer = JsonTracer::new(Vec::new());
+    interpreter
+        .run_traced(&mut crate::BufIo::new(&[]), &mut tracer)
+        .unwrap();
+
+    let json = String::from_utf8(tracer.into_inner()).unwrap();
+    assert_eq!(
+        "{\"step\":0,\"address\":0,\"opcode\":1101,\"op\":\"ADD\",\"modes\":[1,1,0],\
+         \"operands\":[{\"mode\":1,\"raw\":2,\"value\":2},{\"mode\":1,\"raw\":-3,\"value\":-3},\
+         {\"mode\":0,\"raw\":5,\"address\":5}],\"write\":{\"address\":5,\"value\":-1},\"base\":0}\n\
+         {\"step\":1,\"address\":4,\"opcode\":99,\"op\":\"HLT\",\"modes\":[0,0,0],\
+         \"operands\":[],\"write\":null,\"base\":0}\n",
+        json
+    );
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Operand<C = isize> {
+    pub mode: u8,
+    pub raw: C,
+    // The address the operand refers to, None for immediates.
+    pub address: Option<usize>,
+    // The value that was read, None for the operand that is written to.
+    pub value: Option<C>,
+}
+
+// A single executed instruction. The operands are resolved before the instruction executes and
+// the relative base is the one the instruction saw.
+#[derive(PartialEq, Debug, Clone)]
+pub struct TraceRecord<C = isize> {
+    pub step: u64,
+    pub address: usize,
+    pub opcode: isize,
+    pub op: Option<Op>,
+    pub modes: [u8; 3],
+    pub operands: Vec<Operand<C>>,
+    pub write: Option<(usize, C)>,
+    pub base: isize,
+}
+
+pub trait Tracer<C = isize> {
+    fn trace(&mut self, record: &TraceRecord<C>);
+}
+
+impl<C: Cell> Tracer<C> for Vec<TraceRecord<C>> {
+    fn trace(&mut self, record: &TraceRecord<C>) {
+        self.push(record.clone());
+    }
+}
+
+pub(crate) fn begin<C: Cell>(interpreter: &Intcode<C>) -> Option<TraceRecord<C>> {
+    let memory = &interpreter.memory;
+    let address = interpreter.iptr;
+    let opcode = memory.get(address).ok()?.to_isize()?;
+    let op = Op::from_code(opcode % 100);
+
+    let mut modes = [0; 3];
+    let mut rest = opcode / 100;
+    for m in modes.iter_mut() {
+        *m = (rest % 10) as u8;
+        rest /= 10;
+    }
+
+    // Extensions declare their parameters when they are registered.
+    let code = u8::try_from(opcode % 100).ok();
+    let registered = code.and_then(|code| interpreter.extensions.get(&code));
+    let (arity, output) = match (op, registered) {
+        (Some(op), _) => (op.arity(), op.output()),
+        (None, Some(r)) => (
+            r.params.len(),
+            r.params.iter().position(|&p| p == Param::Write),
+        ),
+        (None, None) => (0, None),
+    };
+    let mut operands = Vec::with_capacity(arity);
+    for (i, &mode) in modes.iter().enumerate().take(arity) {
+        // A parameter beyond the memory limit is traced without address and value.
+        let raw = match memory.get(address + i + 1) {
+            Ok(raw) => raw,
+            Err(_) => {
+                operands.push(Operand {
+                    mode,
+                    raw: C::default(),
+                    address: None,
+                    value: None,
+                });
+                continue;
+            }
+        };
+        let target = match mode {
+            0 => raw.to_isize(),
+            2 => raw.to_isize().and_then(|r| interpreter.base.checked_add(r)),
+            _ => None,
+        };
+        let target = target.filter(|&a| a >= 0).map(|a| a as usize);
+
+        let value = if output == Some(i) {
+            None
+        } else if mode == 1 {
+            Some(raw)
+        } else {
+            target.and_then(|a| memory.get(a).ok())
+        };
+
+        operands.push(Operand {
+            mode,
+            raw,
+            address: target,
+            value,
+        });
+    }
+
+    Some(TraceRecord {
+        step: interpreter.retired,
+        address,
+        opcode,
+        op,
+        modes,
+        operands,
+        write: None,
+        base: interpreter.base,
+    })
+}
+
+// Writes one JSON object per executed instruction. Write errors are reported once the tracer
+// is finished.
+pub struct JsonTracer<W: Write> {
+    writer: W,
+    error: Option<std::io::Error>,
+}
+
+impl<W: Write> JsonTracer<W> {
+    pub fn new(writer: W) -> JsonTracer<W> {
+        JsonTracer {
+            writer,
+            error: None,
+        }
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => {
+                self.writer.flush()?;
+                Ok(self.writer)
+            }
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn format_json<C: Cell>(record: &TraceRecord<C>) -> String {
+    let operands = record
+        .operands
+        .iter()
+        .map(|o| {
+            let mut json = format!("{{\"mode\":{},\"raw\":{}", o.mode, o.raw);
+            if let Some(a) = o.address {
+                json.push_str(&format!(",\"address\":{}", a));
+            }
+            if let Some(v) = o.value {
+                json.push_str(&format!(",\"value\":{}", v));
+            }
+            json.push('}');
+            json
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let op = match record.op {
+        Some(op) => format!("\"{}\"", op.name()),
+        None => "null".to_string(),
+    };
+
+    let write = match record.write {
+        Some((a, v)) => format!("{{\"address\":{},\"value\":{}}}", a, v),
+        None => "null".to_string(),
+    };
+
+    format!(
+        "{{\"step\":{},\"address\":{},\"opcode\":{},\"op\":{},\"modes\":[{},{},{}],\
+         \"operands\":[{}],\"write\":{},\"base\":{}}}",
+        record.step,
+        record.address,
+        record.opcode,
+        op,
+        record.modes[0],
+        record.modes[1],
+        record.modes[2],
+        operands,
+        write,
+        record.base
+    )
+}
+
+impl<W: Write, C: Cell> Tracer<C> for JsonTracer<W> {
+    fn trace(&mut self, record: &TraceRecord<C>) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = writeln!(self.writer, "{}", format_json(record)) {
+            self.error = Some(e);
+        }
+    }
+}
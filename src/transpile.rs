@@ -0,0 +1,348 @@
+// Translates an Intcode program into Rust source. Every basic block of the control-flow graph
+// becomes an arm of a dispatch loop over the instruction pointer. The generated code hands over
+// to the interpreter, continuing with the current memory, instruction pointer and relative base,
+// when
+//
+//   - the program writes into a cell of translated code,
+//   - it jumps to an address that was not translated,
+//   - an instruction would fault, so the interpreter reports the error,
+//   - reading input fails, so the interpreter reports it or stops waiting for input,
+//   - memory beyond NATIVE_MEMORY is accessed.
+//
+// The translation uses the program as given, so patching cells of the program before running it
+// is not possible with the generated code. Generated code cannot be resumed either: run always
+// starts from the program, and when it runs out of input it returns Ok(State::Input) and drops the
+// machine, so it suits programs that get all their input up front.
+
+use crate::cfg::{Cfg, Edge};
+use crate::disasm::{Instruction, Op, Param};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+#[test]
+fn test_code_ranges() {
+    // A call to a function whose return address is pushed as an immediate.
+    let program = crate::asm::assemble(
+        "
+                ARB #100
+                ADD #ret, #0, [rb+0]
+                JZ #0, #double
+        ret:    OUT [rb+1]
+                HLT
+        double: IN [rb+1]
+                MUL [rb+1], #2, [rb+1]
+                JZ #0, [rb+0]
+        ",
+    )
+    .unwrap();
+
+    let cfg = translated_cfg(&program);
+    assert_eq!(
+        vec![0, 9, 12],
+        cfg.blocks.keys().copied().collect::<Vec<_>>()
+    );
+    assert_eq!(vec![(0, 20)], code_ranges(&cfg));
+}
+
+#[test]
+fn test_transpile() {
+    let source = transpile(&[3, 9, 1008, 9, 7, 10, 4, 10, 99, 0, 0], false);
+
+    assert!(source.contains("const PROGRAM: &[isize] = &[3, 9, 1008, 9, 7, 10, 4, 10, 99, 0, 0];"));
+    assert!(source.contains("matches!(address, 0..=8)"));
+    assert!(source.contains(
+        "                // 2: EQ [9], #7, [10]\n                \
+                         let x: isize = native!(m.read(9), 2);\n                \
+                         let y: isize = 7;\n                \
+                         let t = native!(m.target(10), 2);\n                \
+                         m.memory[t] = (x == y) as isize;\n"
+    ));
+    assert!(!source.contains("fn main"));
+    assert!(transpile(&[99], true).contains("fn main"));
+}
+
+// Cells beyond this address are left to the interpreter, which keeps sparse memory.
+const NATIVE_MEMORY: usize = 1 << 20;
+
+// Control-flow graph of the code to translate. Besides the code reachable from address 0 it
+// contains the instructions after unconditional jumps if their address appears as an immediate
+// operand, as that is how return addresses are pushed before calling a function.
+fn translated_cfg(program: &[isize]) -> Cfg {
+    let mut entries = vec![0];
+    loop {
+        let cfg = Cfg::build_from(program, &entries);
+        let instructions = || cfg.blocks.values().flat_map(|b| &b.instructions);
+
+        let immediates: BTreeSet<_> = instructions()
+            .flat_map(|(_, instr)| &instr.params)
+            .filter_map(|p| match p {
+                Param::Immediate(v) if *v >= 0 => Some(*v as usize),
+                _ => None,
+            })
+            .collect();
+
+        let returns: Vec<_> = cfg
+            .blocks
+            .values()
+            .filter(|b| !b.invalid && b.edges.iter().all(|e| matches!(e, Edge::Jump(_))))
+            .filter_map(|b| b.instructions.last())
+            .filter(|(_, instr)| instr.op != Op::Hlt)
+            .map(|(address, instr)| address + instr.size())
+            .filter(|a| immediates.contains(a) && !cfg.blocks.contains_key(a))
+            .filter(|&a| program.get(a..).and_then(Instruction::decode).is_some())
+            .collect();
+
+        if returns.is_empty() {
+            return cfg;
+        }
+        entries.extend(returns);
+    }
+}
+
+// Address ranges (inclusive) covered by translated instructions.
+fn code_ranges(cfg: &Cfg) -> Vec<(usize, usize)> {
+    let cells: BTreeSet<_> = cfg
+        .blocks
+        .values()
+        .flat_map(|b| &b.instructions)
+        .flat_map(|(address, instr)| *address..(address + instr.size()))
+        .collect();
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for cell in cells {
+        match ranges.last_mut() {
+            Some(range) if range.1 + 1 == cell => range.1 = cell,
+            _ => ranges.push((cell, cell)),
+        }
+    }
+    ranges
+}
+
+// Expression reading the parameter, failing over to the interpreter at address.
+fn read(param: Param, address: usize) -> String {
+    match param {
+        Param::Position(a) => format!("native!(m.read({}), {})", a, address),
+        Param::Immediate(v) => v.to_string(),
+        Param::Relative(o) => format!("native!(m.read_relative({}), {})", o, address),
+    }
+}
+
+// Statement binding the index of the memory cell the parameter writes to.
+fn target(param: Param, address: usize) -> String {
+    match param {
+        Param::Relative(o) => format!("let t = native!(m.target_relative({}), {});", o, address),
+        p => format!("let t = native!(m.target({}), {});", p.value(), address),
+    }
+}
+
+// Translates a single instruction. Returns the statements and, for jumps, the expression for
+// the next instruction pointer.
+fn translate(address: usize, instr: &Instruction) -> (Vec<String>, Option<String>) {
+    let p = &instr.params;
+    let next = address + instr.size();
+    let binary = |op: &str| {
+        vec![
+            format!("let x: isize = {};", read(p[0], address)),
+            format!("let y: isize = {};", read(p[1], address)),
+            target(p[2], address),
+            format!("m.memory[t] = {};", op),
+        ]
+    };
+
+    match instr.op {
+        Op::Add => (binary("x.wrapping_add(y)"), None),
+        Op::Mul => (binary("x.wrapping_mul(y)"), None),
+        Op::Lt => (binary("(x < y) as isize"), None),
+        Op::Eq => (binary("(x == y) as isize"), None),
+        Op::In => (
+            vec![
+                target(p[0], address),
+                format!("m.memory[t] = native!(io.input().ok(), {});", address),
+            ],
+            None,
+        ),
+        Op::Out => (vec![format!("io.output({});", read(p[0], address))], None),
+        Op::Arb => (
+            vec![format!(
+                "m.base = native!(m.base.checked_add({}), {});",
+                read(p[0], address),
+                address
+            )],
+            None,
+        ),
+        Op::Jnz | Op::Jz => {
+            let jump = match p[1] {
+                Param::Immediate(t) if t >= 0 => t.to_string(),
+                p => format!(
+                    "native!(usize::try_from({}).ok(), {})",
+                    read(p, address),
+                    address
+                ),
+            };
+            let condition = if instr.op == Op::Jnz { "!=" } else { "==" };
+            let next = match p[0] {
+                Param::Immediate(v) if (v != 0) == (instr.op == Op::Jnz) => jump,
+                Param::Immediate(_) => next.to_string(),
+                c => format!(
+                    "if {} {} 0 {{ {} }} else {{ {} }}",
+                    read(c, address),
+                    condition,
+                    jump,
+                    next
+                ),
+            };
+            (vec![], Some(next))
+        }
+        Op::Hlt => (vec!["return Ok(State::Terminated);".to_string()], None),
+    }
+}
+
+static HEADER: &str = "\
+// Generated by intcode-transpile, do not edit.
+
+use intcode::{Intcode, IntcodeError, Io, State};
+";
+
+static MACHINE: &str = "
+struct Machine {
+    memory: Vec<isize>,
+    base: isize,
+}
+
+#[allow(dead_code)]
+impl Machine {
+    fn read(&self, address: isize) -> Option<isize> {
+        if address < 0 || address as usize >= NATIVE_MEMORY {
+            return None;
+        }
+        Some(self.memory.get(address as usize).copied().unwrap_or(0))
+    }
+
+    fn read_relative(&self, offset: isize) -> Option<isize> {
+        self.read(self.base.checked_add(offset)?)
+    }
+
+    // Index of a cell that may be written by native code.
+    fn target(&mut self, address: isize) -> Option<usize> {
+        if address < 0 || address as usize >= NATIVE_MEMORY || is_code(address as usize) {
+            return None;
+        }
+        if self.memory.len() <= address as usize {
+            self.memory.resize(address as usize + 1, 0);
+        }
+        Some(address as usize)
+    }
+
+    fn target_relative(&mut self, offset: isize) -> Option<usize> {
+        self.target(self.base.checked_add(offset)?)
+    }
+}
+
+// Evaluates to the contained value, or leaves the dispatch loop to continue in the interpreter
+// at the given address.
+#[allow(unused_macros)]
+macro_rules! native {
+    ($value:expr, $address:expr) => {
+        match $value {
+            Some(v) => v,
+            None => break $address,
+        }
+    };
+}
+";
+
+static RUN_END: &str = "            _ => break pc,
+        };
+    };
+
+    Intcode::from_state(m.memory, fallback, m.base).run(io)
+}
+";
+
+static MAIN: &str = "
+fn main() {
+    match run(&mut intcode::StdIo::new()) {
+        Ok(State::Input) => panic!(\"Input starved\"),
+        Ok(_) => (),
+        Err(e) => panic!(\"{}\", e),
+    }
+}
+";
+
+// Generates the Rust source for the program. It provides `pub fn run(io: &mut dyn Io) ->
+// Result<State, IntcodeError>`, returning like Intcode::run except that Ok(State::Input) is
+// final, with_main adds a main function that runs the program on StdIo.
+pub fn transpile(program: &[isize], with_main: bool) -> String {
+    let cfg = translated_cfg(program);
+    let mut source = HEADER.to_string();
+
+    let cells = program
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(source, "\nconst PROGRAM: &[isize] = &[{}];", cells).unwrap();
+    writeln!(source, "const NATIVE_MEMORY: usize = {};", NATIVE_MEMORY).unwrap();
+
+    let ranges = code_ranges(&cfg)
+        .iter()
+        .map(|(first, last)| format!("{}..={}", first, last))
+        .collect::<Vec<_>>();
+    let is_code = if ranges.is_empty() {
+        "false".to_string()
+    } else {
+        format!("matches!(address, {})", ranges.join(" | "))
+    };
+    writeln!(
+        source,
+        "\nfn is_code(address: usize) -> bool {{\n    {}\n}}",
+        is_code
+    )
+    .unwrap();
+
+    source.push_str(MACHINE);
+    source.push_str(
+        "
+// Runs the program from the start. Running out of input ends the run for good, the machine
+// waiting for it is dropped. Programs without jumps never reach the end of the dispatch loop.
+#[allow(unreachable_code, unused_mut)]
+pub fn run(io: &mut dyn Io) -> Result<State, IntcodeError> {
+    let mut m = Machine {
+        memory: PROGRAM.to_vec(),
+        base: 0,
+    };
+
+    let mut pc: usize = 0;
+    let fallback = loop {
+        pc = match pc {
+",
+    );
+
+    for block in cfg.blocks.values().filter(|b| !b.invalid) {
+        writeln!(source, "            {} => {{", block.start).unwrap();
+
+        let mut next = None;
+        for (address, instr) in &block.instructions {
+            writeln!(source, "                // {}: {}", address, instr).unwrap();
+            let (statements, jump) = translate(*address, instr);
+            for statement in statements {
+                writeln!(source, "                {}", statement).unwrap();
+            }
+            next = Some(jump.unwrap_or_else(|| (address + instr.size()).to_string()));
+        }
+
+        if block.instructions.last().map(|(_, i)| i.op) != Some(Op::Hlt) {
+            writeln!(source, "                {}", next.unwrap()).unwrap();
+        }
+        writeln!(source, "            }}").unwrap();
+    }
+
+    source.push_str(RUN_END);
+    if source.contains("try_from") {
+        source = source.replacen("State};\n", "State};\nuse std::convert::TryFrom;\n", 1);
+    }
+    if with_main {
+        source.push_str(MAIN);
+    }
+    source
+}
@@ -0,0 +1,110 @@
+use crate::{Cell, Memory};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+#[test]
+fn test_hash() {
+    let mut memory = Memory::new(vec![1, 0, 2], 100);
+    let mut detector = LoopDetector::new(&memory);
+
+    detector.write(50, memory.get(50).unwrap(), 7);
+    memory.set(50, 7).unwrap();
+    detector.write(0, memory.get(0).unwrap(), 0);
+    memory.set(0, 0).unwrap();
+
+    assert_eq!(LoopDetector::new(&memory).hash, detector.hash);
+}
+
+// Detects a machine that returns to an identical state (memory, instruction pointer and relative
+// base) without performing I/O, using Brent's cycle detection: the state is compared against a
+// checkpoint that is moved forward after 1, 2, 4, ... instructions. Memory is only compared if a
+// hash over all cells matches, the hash is updated on every write.
+#[derive(Clone)]
+pub(crate) struct LoopDetector<C: Cell> {
+    hash: u64,
+    checkpoint: Option<Checkpoint<C>>,
+    steps: u64,
+    power: u64,
+}
+
+#[derive(Clone)]
+struct Checkpoint<C: Cell> {
+    memory: Memory<C>,
+    iptr: usize,
+    base: isize,
+    hash: u64,
+}
+
+// Contribution of a cell to the memory hash. Cells that are zero contribute nothing, so the hash
+// does not depend on which pages are allocated.
+fn cell_hash<C: Cell>(address: usize, value: C) -> u64 {
+    if value == C::default() {
+        return 0;
+    }
+    let mut hasher = DefaultHasher::new();
+    (address, value).hash(&mut hasher);
+    hasher.finish()
+}
+
+impl<C: Cell> LoopDetector<C> {
+    pub fn new(memory: &Memory<C>) -> LoopDetector<C> {
+        let hash = memory
+            .segments()
+            .into_iter()
+            .flat_map(|(start, cells)| {
+                cells
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, &v)| cell_hash(start + i, v))
+            })
+            .fold(0, u64::wrapping_add);
+
+        LoopDetector {
+            hash,
+            checkpoint: None,
+            steps: 0,
+            power: 1,
+        }
+    }
+
+    pub fn write(&mut self, address: usize, old: C, new: C) {
+        self.hash = self
+            .hash
+            .wrapping_sub(cell_hash(address, old))
+            .wrapping_add(cell_hash(address, new));
+    }
+
+    // Called on I/O, states before it can no longer be revisited without I/O.
+    pub fn reset(&mut self) {
+        self.checkpoint = None;
+        self.power = 1;
+    }
+
+    // Returns true if the given state equals the checkpoint.
+    pub fn check(&mut self, memory: &Memory<C>, iptr: usize, base: isize) -> bool {
+        if let Some(c) = &self.checkpoint {
+            if c.iptr == iptr
+                && c.base == base
+                && c.hash == self.hash
+                && c.memory.segments() == memory.segments()
+            {
+                return true;
+            }
+
+            self.steps += 1;
+            if self.steps < self.power {
+                return false;
+            }
+            self.power *= 2;
+        }
+
+        self.steps = 0;
+        self.checkpoint = Some(Checkpoint {
+            memory: memory.clone(),
+            iptr,
+            base,
+            hash: self.hash,
+        });
+        false
+    }
+}
@@ -0,0 +1,176 @@
+// The interpreter as it was before the decode cache and the paged memory, kept to compare the
+// current one against. Only the parts needed to run programs remain, faults panic.
+
+use intcode::{Io, State};
+
+pub struct Intcode {
+    instructions: Vec<isize>,
+    base: isize,
+    iptr: usize,
+    input_requested: bool,
+}
+
+fn decode_opcode(mut op: isize) -> [u8; 4] {
+    let mut result = [0u8; 4];
+
+    let mut div = 10000;
+    for i in 0..3 {
+        let tmp = op / div;
+        op -= tmp * div;
+        div /= 10;
+        result[3 - i] = tmp as u8;
+    }
+
+    result[0] = op as u8;
+
+    result
+}
+
+pub fn evaluate_io(instructions: Vec<isize>, io: &mut dyn Io) -> isize {
+    let mut interpreter = Intcode::new(instructions);
+
+    let mut input = 0;
+    loop {
+        match interpreter.step(input) {
+            State::Input => input = io.input().unwrap(),
+            State::Output(o) => io.output(o),
+            State::Terminated => break,
+        }
+    }
+    interpreter.instructions[0]
+}
+
+impl Intcode {
+    pub fn new(mut instructions: Vec<isize>) -> Intcode {
+        instructions.extend([0; 10000].iter());
+        Intcode {
+            instructions,
+            base: 0,
+            iptr: 0,
+            input_requested: false,
+        }
+    }
+
+    fn decode_opcode(&self) -> [u8; 4] {
+        decode_opcode(self.instructions[self.iptr])
+    }
+
+    fn load_argument(&self, pos: usize, mode: &[u8; 4]) -> isize {
+        let value = self.instructions[self.iptr + pos];
+
+        match mode[pos] {
+            0 => {
+                if value < 0 {
+                    panic!("Encountered negative position!");
+                }
+                self.instructions[value as usize]
+            }
+            1 => value,
+            2 => self.instructions[(self.base + value) as usize],
+            _ => panic!("Unhandled parameter mode!"),
+        }
+    }
+
+    fn store(&mut self, pos: usize, value: isize, opcode: &[u8; 4]) {
+        let address = self.instructions[self.iptr + pos];
+
+        match opcode[pos] {
+            0 => {
+                if address < 0 {
+                    panic!("Encountered negative position!");
+                }
+                self.instructions[address as usize] = value;
+            }
+            1 => panic!("Absolute mode not supported for store operation"),
+            2 => {
+                self.instructions[(self.base + address) as usize] = value;
+            }
+            _ => panic!("Unhandled parameter mode!"),
+        }
+    }
+
+    fn load_ptr(&self, pos: usize, opcode: &[u8; 4]) -> usize {
+        let ptr = self.load_argument(pos, opcode);
+
+        if ptr < 0 {
+            panic!("Invalid instruction pointer!")
+        }
+        ptr as usize
+    }
+
+    pub fn step(&mut self, input: isize) -> State {
+        while self.iptr < self.instructions.len() {
+            let opcode = self.decode_opcode();
+
+            let stride = match opcode[0] {
+                1 => {
+                    let s1 = self.load_argument(1, &opcode);
+                    let s2 = self.load_argument(2, &opcode);
+                    self.store(3, s1 + s2, &opcode);
+                    4
+                }
+                2 => {
+                    let s1 = self.load_argument(1, &opcode);
+                    let s2 = self.load_argument(2, &opcode);
+                    self.store(3, s1 * s2, &opcode);
+                    4
+                }
+                3 => {
+                    if self.input_requested {
+                        self.store(1, input, &opcode);
+                        self.input_requested = false;
+                        2
+                    } else {
+                        self.input_requested = true;
+                        return State::Input;
+                    }
+                }
+                4 => {
+                    let result = self.load_argument(1, &opcode);
+                    self.iptr += 2;
+                    return State::Output(result);
+                }
+                5 => {
+                    if self.load_argument(1, &opcode) != 0 {
+                        self.iptr = self.load_ptr(2, &opcode);
+                        0
+                    } else {
+                        3
+                    }
+                }
+                6 => {
+                    if self.load_argument(1, &opcode) == 0 {
+                        self.iptr = self.load_ptr(2, &opcode);
+                        0
+                    } else {
+                        3
+                    }
+                }
+                7 => {
+                    let s1 = self.load_argument(1, &opcode);
+                    let s2 = self.load_argument(2, &opcode);
+
+                    self.store(3, (s1 < s2) as isize, &opcode);
+                    4
+                }
+                8 => {
+                    let s1 = self.load_argument(1, &opcode);
+                    let s2 = self.load_argument(2, &opcode);
+
+                    self.store(3, (s1 == s2) as isize, &opcode);
+                    4
+                }
+                9 => {
+                    self.base += self.load_argument(1, &opcode);
+                    2
+                }
+                99 => return State::Terminated,
+                o => panic!("Unhandled opcode {}", o),
+            };
+
+            self.iptr += stride;
+        }
+
+        panic!("Reached end of memory!");
+    }
+}
@@ -0,0 +1,211 @@
+// Compares the interpreter with the decode cache, without it and the interpreter from before
+// both the cache and the paged memory on the workloads of days 19 and 23. Ignored by default, run
+// it with
+//
+//   cargo test --release --test decode_cache -- --ignored --nocapture
+//
+// The workloads use the programs in tests/programs unless INTCODE_DAY19 and INTCODE_DAY23 name
+// puzzle inputs of the two days.
+
+mod baseline;
+
+use intcode::scheduler::{Event, Policy, Route, Scheduler};
+use intcode::{BufIo, Intcode, State};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug)]
+enum Engine {
+    Baseline,
+    Uncached,
+    Cached,
+}
+
+static ENGINES: [Engine; 3] = [Engine::Baseline, Engine::Uncached, Engine::Cached];
+
+fn load(var: &str, path: &str) -> Vec<isize> {
+    if let Ok(input) = std::env::var(var) {
+        return intcode::read_intcode_file(&input);
+    }
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+    intcode::asm::assemble(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn machine(program: &[isize], engine: Engine) -> Intcode {
+    let mut interpreter = Intcode::new(program.to_vec());
+    interpreter.set_decode_cache(matches!(engine, Engine::Cached));
+    interpreter
+}
+
+fn query(program: &[isize], engine: Engine, x: isize, y: isize) -> isize {
+    let point = [x, y];
+    let mut io = BufIo::new(&point);
+    match engine {
+        Engine::Baseline => {
+            baseline::evaluate_io(program.to_vec(), &mut io);
+        }
+        _ => {
+            machine(program, engine).run(&mut io).unwrap();
+        }
+    }
+    io.get(0)
+}
+
+// Both parts of day 19 the way the solution does them, a new machine for every point.
+fn tractor(program: &[isize], engine: Engine) -> (isize, isize) {
+    let mut sum = 0;
+    for y in 0..50 {
+        for x in 0..50 {
+            sum += query(program, engine, x, y);
+        }
+    }
+
+    let (mut min_x, mut max_x) = (0, 0);
+    for y in 10.. {
+        let mut x = min_x;
+        while query(program, engine, x, y) == 0 {
+            x += 1;
+        }
+        min_x = x;
+        x = std::cmp::max(max_x, x);
+        while query(program, engine, x, y) == 1 {
+            x += 1;
+        }
+        max_x = x;
+
+        if max_x - min_x >= 100
+            && (max_x - 100..max_x).all(|x| query(program, engine, x, y + 99) == 1)
+        {
+            return (sum, (max_x - 100) * 10000 + y);
+        }
+    }
+    unreachable!()
+}
+
+struct Node {
+    interpreter: baseline::Intcode,
+    queue: VecDeque<isize>,
+    state: State,
+}
+
+// Day 23 with the round robin loop of its original solution.
+fn baseline_network(program: &[isize], rounds: usize) -> Vec<[isize; 2]> {
+    let mut nodes: Vec<_> = (0..50)
+        .map(|i| {
+            let mut interpreter = baseline::Intcode::new(program.to_vec());
+            assert_eq!(State::Input, interpreter.step(0));
+            let state = interpreter.step(i);
+            Node {
+                interpreter,
+                queue: VecDeque::new(),
+                state,
+            }
+        })
+        .collect();
+
+    let mut nat = Vec::new();
+    while nat.len() <= rounds {
+        let mut idle = true;
+        for i in 0..nodes.len() {
+            while let State::Output(address) = nodes[i].state {
+                let x = nodes[i].interpreter.step(0);
+                let y = nodes[i].interpreter.step(0);
+                nodes[i].state = nodes[i].interpreter.step(0);
+                let packet = match (x, y) {
+                    (State::Output(x), State::Output(y)) => [x, y],
+                    _ => panic!("Incomplete packet from {}", i),
+                };
+                idle = false;
+                match address {
+                    255 => nat.push(packet),
+                    address => nodes[address as usize].queue.extend(&packet),
+                }
+            }
+
+            let node = &mut nodes[i];
+            if node.state == State::Input && node.queue.is_empty() {
+                node.state = node.interpreter.step(-1);
+            }
+            while node.state == State::Input && !node.queue.is_empty() {
+                idle = false;
+                node.state = node.interpreter.step(node.queue.pop_front().unwrap());
+            }
+        }
+
+        if idle {
+            if let Some(&packet) = nat.last() {
+                nodes[0].queue.extend(&packet);
+            }
+        }
+    }
+    nat
+}
+
+// Day 23 on the scheduler, the NAT sends its last packet to address 0 whenever the network is
+// idle.
+fn network(program: &[isize], engine: Engine, rounds: usize) -> Vec<[isize; 2]> {
+    let mut network = Scheduler::new(Policy::UntilBlocked);
+    network.set_idle_input(Some(-1));
+    network.set_router(3, |_, packet| match packet[0] {
+        255 => Route::External,
+        address => Route::To(address as usize, packet[1..].to_vec()),
+    });
+    for i in 0..50 {
+        let node = network.add(machine(program, engine));
+        network.send(node, &[i]);
+    }
+
+    let mut nat = Vec::new();
+    while nat.len() <= rounds {
+        match network.run().unwrap() {
+            Event::Output(_, packet) => nat.push([packet[1], packet[2]]),
+            Event::Idle => {
+                if let Some(&packet) = nat.last() {
+                    network.send(0, &packet);
+                }
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+    nat
+}
+
+fn time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+// The engines take turns so that changes in the speed of the machine affect all of them alike,
+// the fastest of five runs counts.
+fn bench<T: PartialEq + std::fmt::Debug>(name: &str, run: impl Fn(Engine) -> T) {
+    let expected = run(Engine::Baseline);
+    let mut best = [Duration::MAX; 3];
+    for _ in 0..5 {
+        for (i, &engine) in ENGINES.iter().enumerate() {
+            let (result, elapsed) = time(|| run(engine));
+            assert_eq!(expected, result, "{:?}", engine);
+            best[i] = std::cmp::min(best[i], elapsed);
+        }
+    }
+
+    let speedup = best[0].as_secs_f64() / best[2].as_secs_f64();
+    println!(
+        "{}: baseline {:?}, uncached {:?}, cached {:?}, {:.2}x faster than baseline",
+        name, best[0], best[1], best[2], speedup
+    );
+}
+
+#[test]
+#[ignore]
+fn bench_decode_cache() {
+    let program = load("INTCODE_DAY19", "tests/programs/beam.asm");
+    bench("Day 19", |engine| tractor(&program, engine));
+
+    let program = load("INTCODE_DAY23", "tests/programs/nic.asm");
+    bench("Day 23", |engine| match engine {
+        Engine::Baseline => baseline_network(&program, 20_000)[0],
+        _ => network(&program, engine, 20_000)[0],
+    });
+}
@@ -0,0 +1,30 @@
+; Outputs 1 if the point (x, y) read from the input lies within a beam between the slopes
+; 0.7 and 1.3, like the drone program of day 19. Multiplies x and y by repeated addition
+; first, so that a query executes a few hundred instructions.
+                IN [x]
+                IN [y]
+                ADD #0, #0, [i]
+                ADD #0, #0, [p]
+loop:           LT [i], [y], [c]
+                JZ [c], #slopes
+                ADD [p], [x], [p]
+                ADD [i], #1, [i]
+                JNZ #1, #loop
+slopes:         MUL [x], #7, [a]
+                MUL [y], #10, [b]
+                LT [b], [a], [c]
+                JNZ [c], #outside
+                MUL [x], #13, [a]
+                LT [a], [b], [c]
+                JNZ [c], #outside
+                OUT #1
+                HLT
+outside:        OUT #0
+                HLT
+x:              .data 0
+y:              .data 0
+i:              .data 0
+p:              .data 0
+a:              .data 0
+b:              .data 0
+c:              .data 0
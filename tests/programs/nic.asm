@@ -0,0 +1,20 @@
+; A network interface like the ones of day 23. Reads its address, the one at address 0 sends
+; (0, 7) to the NAT at 255. Then polls for packets and forwards every one it receives to the
+; NAT.
+                IN [id]
+                JNZ [id], #poll
+                OUT #255
+                OUT #0
+                OUT #7
+poll:           IN [x]
+                EQ [x], #-1, [c]
+                JNZ [c], #poll
+                IN [y]
+                OUT #255
+                OUT [x]
+                OUT [y]
+                JNZ #1, #poll
+id:             .data 0
+x:              .data 0
+y:              .data 0
+c:              .data 0
@@ -0,0 +1,195 @@
+// Compiles transpiled programs with rustc and compares them with the interpreter. The benchmark
+// is ignored by default as it takes a while, run it with
+//
+//   cargo test --release --test transpile -- --ignored --nocapture
+//
+// The release build makes the timings of bench_beam comparable.
+
+use intcode::transpile::transpile;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+// Runs the program on the inputs given as arguments and prints the outputs.
+static EXAMPLE_MAIN: &str = "
+fn main() {
+    let inputs: Vec<isize> = std::env::args().skip(1).map(|a| a.parse().unwrap()).collect();
+    let mut io = intcode::BufIo::new(&inputs);
+    assert_eq!(State::Terminated, run(&mut io).unwrap());
+    println!(\"{:?}\", io.output());
+}
+";
+
+// Scans the beam on a square with the size given as argument, prints the number of points
+// within the beam and the time it took in microseconds.
+static BEAM_MAIN: &str = "
+fn main() {
+    let size: isize = std::env::args().nth(1).unwrap().parse().unwrap();
+    let start = std::time::Instant::now();
+    let mut sum = 0;
+    for y in 0..size {
+        for x in 0..size {
+            let point = [x, y];
+            let mut io = intcode::BufIo::new(&point);
+            run(&mut io).unwrap();
+            sum += io.get(0);
+        }
+    }
+    println!(\"{} {}\", sum, start.elapsed().as_micros());
+}
+";
+
+fn assemble(path: &str) -> Vec<isize> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+    intcode::asm::assemble(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+// The most recently built library in the directory of the test binary.
+fn library() -> (PathBuf, PathBuf) {
+    let deps = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    let rlib = std::fs::read_dir(&deps)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            name.starts_with("libintcode-") && name.ends_with(".rlib")
+        })
+        .max_by_key(|path| path.metadata().unwrap().modified().unwrap())
+        .expect("The intcode library has not been built");
+    (deps, rlib)
+}
+
+// A program with its name and the inputs of the runs to compare.
+type Example = (&'static str, Vec<isize>, Vec<Vec<isize>>);
+
+fn compile(name: &str, program: &[isize], main: &str, optimize: bool) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let source = dir.join(format!("{}.rs", name));
+    std::fs::write(&source, transpile(program, false) + main).unwrap();
+
+    let (deps, rlib) = library();
+    let binary = dir.join(name);
+    let status = Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()))
+        .args(["--edition", "2018", "-C"])
+        .arg(if optimize {
+            "opt-level=3"
+        } else {
+            "opt-level=0"
+        })
+        .arg("-o")
+        .arg(&binary)
+        .arg("-L")
+        .arg(format!("dependency={}", deps.display()))
+        .arg("--extern")
+        .arg(format!("intcode={}", rlib.display()))
+        .arg(&source)
+        .status()
+        .unwrap();
+    assert!(status.success(), "Could not compile {}", source.display());
+    binary
+}
+
+fn execute(binary: &Path, args: &[isize]) -> String {
+    let output = Command::new(binary)
+        .args(args.iter().map(|a| a.to_string()))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_transpiled_examples() {
+    let examples: Vec<Example> = vec![
+        // Compares the input with 8, from day 5.
+        (
+            "compare",
+            vec![
+                3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36,
+                98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000,
+                1, 20, 4, 20, 1105, 1, 46, 98, 99,
+            ],
+            vec![vec![7], vec![8], vec![9]],
+        ),
+        // Outputs itself, from day 9.
+        (
+            "quine",
+            vec![
+                109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+            ],
+            vec![vec![]],
+        ),
+        (
+            "large",
+            vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0],
+            vec![vec![]],
+        ),
+        // Writes into its own code, which continues in the interpreter.
+        (
+            "selfmod",
+            intcode::asm::assemble(
+                "
+                add:    ADD #0, #1, [acc]
+                        OUT [acc]
+                        ADD [acc], #0, [add+1]
+                        LT [acc], #5, [cond]
+                        JNZ [cond], #add
+                        HLT
+                acc:    .data 0
+                cond:   .data 0
+                ",
+            )
+            .unwrap(),
+            vec![vec![]],
+        ),
+        (
+            "beam",
+            assemble("tests/programs/beam.asm"),
+            vec![vec![3, 3], vec![9, 3]],
+        ),
+    ];
+
+    for (name, program, runs) in examples {
+        let binary = compile(name, &program, EXAMPLE_MAIN, false);
+        for inputs in runs {
+            let mut io = intcode::BufIo::new(&inputs);
+            intcode::evaluate_io(program.clone(), &mut io);
+            let expected = format!("{:?}\n", io.output());
+            assert_eq!(expected, execute(&binary, &inputs), "{} {:?}", name, inputs);
+        }
+    }
+}
+
+#[test]
+#[ignore]
+fn bench_beam() {
+    let size = 200;
+    let program = assemble("tests/programs/beam.asm");
+
+    let start = Instant::now();
+    let mut sum = 0;
+    for y in 0..size {
+        for x in 0..size {
+            let point = [x, y];
+            let mut io = intcode::BufIo::new(&point);
+            intcode::evaluate_io(program.clone(), &mut io);
+            sum += io.get(0);
+        }
+    }
+    let interpreted = start.elapsed();
+
+    let binary = compile("beam_bench", &program, BEAM_MAIN, true);
+    let output = execute(&binary, &[size]);
+    let mut words = output.split_whitespace();
+    assert_eq!(Some(sum.to_string().as_str()), words.next());
+    let transpiled = Duration::from_micros(words.next().unwrap().parse().unwrap());
+
+    println!(
+        "Beam scan of {}x{}: interpreted {:?}, transpiled {:?}",
+        size, size, interpreted, transpiled
+    );
+}